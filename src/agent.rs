@@ -1,7 +1,10 @@
+use std::sync::Arc;
 use std::time::Instant;
 
 use macroquad::prelude::*;
+use macroquad::rand::gen_range;
 
+use crate::resources::Resources;
 use crate::*;
 
 pub enum AgentCommand {
@@ -9,58 +12,105 @@ pub enum AgentCommand {
     Right,
     Bottom,
     Top,
+    Stay,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Moves the agents of a run can choose from, `ACTION_SPACE` by default.
+/// Set with `--actions` or in the editor
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ActionSpace {
+    /// Top, left, bottom and right
+    FourWay,
+    /// Four way moves and staying in place
+    FourWayStay,
+    /// Four way and diagonal moves, and staying in place
+    EightWay,
 }
 
 #[derive(Clone)]
 pub struct Agent {
     pub pos: (usize, usize),
+    // Level the agent walks, see `RESOURCES`
+    resources: Arc<Resources>,
     game_size: (usize, usize),
     birth_ts: Instant,
+    // Last move, the agent keeps sliding this way while on ice
+    last_delta: (isize, isize),
+}
+
+impl Default for Agent {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Agent {
     pub fn new() -> Self {
-        let resources = RESOURCES.get().unwrap();
+        Agent::on_level(0)
+    }
+
+    pub fn on_level(level: usize) -> Self {
+        let resources = RESOURCES.level(level).unwrap();
         let lvl = &resources.lvl_info;
         Self {
-            pos: lvl.agent.clone(),
-            game_size: lvl.size.clone(),
+            pos: lvl.agent,
+            game_size: lvl.size,
+            resources: resources.clone(),
             birth_ts: Instant::now(),
+            last_delta: (0, 0),
         }
     }
 
     pub fn update(&mut self, command: AgentCommand, has_all_keys: bool) {
-        let resources = RESOURCES.get().unwrap();
+        let resources = &self.resources;
 
         if self.is_agent_asleep() {
             return;
         }
 
-        // Update player pos
-        let mut new_loc = self.pos.clone();
-        match command {
-            AgentCommand::Left => new_loc = (new_loc.0 - 1, new_loc.1),
-            AgentCommand::Right => new_loc = (new_loc.0 + 1, new_loc.1),
-            AgentCommand::Bottom => new_loc = (new_loc.0, new_loc.1 + 1),
-            AgentCommand::Top => new_loc = (new_loc.0, new_loc.1 - 1),
-        }
+        // Update player pos, the command is ignored while sliding on ice
+        let (dx, dy) = match resources.lvl_info.is_ice(self.pos) && self.last_delta != (0, 0) {
+            true => self.last_delta,
+            false => command.delta(),
+        };
+        self.last_delta = (0, 0);
+        let (Some(x), Some(y)) = (
+            self.pos.0.checked_add_signed(dx),
+            self.pos.1.checked_add_signed(dy),
+        ) else {
+            return;
+        };
+        let new_loc = (x, y);
 
         // Make sure new player pos isnt a wall
-        let is_not_wall = resources
-            .lvl_map
-            .get_tile(LAYER_WALLS, new_loc.0 as u32, new_loc.1 as u32)
-            .is_none();
-        let is_not_door = resources
-            .lvl_map
-            .get_tile(LAYER_DOOR, new_loc.0 as u32, new_loc.1 as u32)
-            .is_none();
-
-        if is_not_wall && (is_not_door || has_all_keys) && self.is_in_bounds(new_loc.0, new_loc.1) {
+        let is_wall = |pos: (usize, usize)| resources.lvl_info.is_wall(pos);
+        let is_not_door = new_loc != resources.lvl_info.door;
+
+        // Diagonal moves can't squeeze between two walls touching at a corner
+        let is_corner_blocked = dx != 0
+            && dy != 0
+            && is_wall((new_loc.0, self.pos.1))
+            && is_wall((self.pos.0, new_loc.1));
+
+        if !is_wall(new_loc)
+            && !is_corner_blocked
+            && (is_not_door || has_all_keys)
+            && self.is_in_bounds(new_loc.0, new_loc.1)
+        {
             self.pos = new_loc;
+            self.last_delta = (dx, dy);
         }
     }
 
     pub fn draw(&self, scale_factor: f32, offset_x: f32, offset_y: f32) {
+        self.draw_tinted(WHITE, scale_factor, offset_x, offset_y);
+    }
+
+    pub fn draw_tinted(&self, tint: Color, scale_factor: f32, offset_x: f32, offset_y: f32) {
         let textures = TEXTURES.get().unwrap();
         let mut texture = match self.birth_ts.elapsed().as_secs() {
             0 => textures.agent_sleep1_texture,
@@ -77,7 +127,7 @@ impl Agent {
             texture,
             self.pos.0 as f32 * scale_factor + offset_x,
             self.pos.1 as f32 * scale_factor + offset_y,
-            WHITE,
+            tint,
             DrawTextureParams {
                 dest_size: Some(Vec2::splat(scale_factor)),
                 ..Default::default()
@@ -90,17 +140,11 @@ impl Agent {
             return false;
         }
 
-        match self.birth_ts.elapsed().as_secs() {
-            0 => true,
-            1 => true,
-            2 => true,
-            3 => true,
-            _ => false,
-        }
+        matches!(self.birth_ts.elapsed().as_secs(), 0..=3)
     }
 
     fn is_in_bounds(&self, x: usize, y: usize) -> bool {
-        x <= self.game_size.0 - 1 && y <= self.game_size.1 - 1
+        x < self.game_size.0 && y < self.game_size.1
     }
 }
 
@@ -110,7 +154,12 @@ impl AgentCommand {
             0 => AgentCommand::Top,
             1 => AgentCommand::Left,
             2 => AgentCommand::Bottom,
-            _ => AgentCommand::Right,
+            3 => AgentCommand::Right,
+            5 => AgentCommand::TopLeft,
+            6 => AgentCommand::TopRight,
+            7 => AgentCommand::BottomLeft,
+            8 => AgentCommand::BottomRight,
+            _ => AgentCommand::Stay,
         }
     }
 
@@ -120,6 +169,74 @@ impl AgentCommand {
             AgentCommand::Left => 1,
             AgentCommand::Bottom => 2,
             AgentCommand::Right => 3,
+            AgentCommand::Stay => 4,
+            AgentCommand::TopLeft => 5,
+            AgentCommand::TopRight => 6,
+            AgentCommand::BottomLeft => 7,
+            AgentCommand::BottomRight => 8,
+        }
+    }
+
+    /// Arrow pointing the way the command moves, for genome strips
+    pub fn arrow(&self) -> char {
+        match self {
+            AgentCommand::Top => '↑',
+            AgentCommand::Left => '←',
+            AgentCommand::Bottom => '↓',
+            AgentCommand::Right => '→',
+            AgentCommand::Stay => '·',
+            AgentCommand::TopLeft => '↖',
+            AgentCommand::TopRight => '↗',
+            AgentCommand::BottomLeft => '↙',
+            AgentCommand::BottomRight => '↘',
         }
     }
+
+    fn delta(&self) -> (isize, isize) {
+        match self {
+            AgentCommand::Top => (0, -1),
+            AgentCommand::Left => (-1, 0),
+            AgentCommand::Bottom => (0, 1),
+            AgentCommand::Right => (1, 0),
+            AgentCommand::Stay => (0, 0),
+            AgentCommand::TopLeft => (-1, -1),
+            AgentCommand::TopRight => (1, -1),
+            AgentCommand::BottomLeft => (-1, 1),
+            AgentCommand::BottomRight => (1, 1),
+        }
+    }
+}
+
+impl ActionSpace {
+    pub const ALL: [ActionSpace; 3] = [
+        ActionSpace::FourWay,
+        ActionSpace::FourWayStay,
+        ActionSpace::EightWay,
+    ];
+
+    /// Name on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            ActionSpace::FourWay => "four",
+            ActionSpace::FourWayStay => "four_stay",
+            ActionSpace::EightWay => "eight",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ActionSpace> {
+        ActionSpace::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    /// Commands are numbered so that each action space is a prefix of the next
+    pub fn num_actions(&self) -> u8 {
+        match self {
+            ActionSpace::FourWay => 4,
+            ActionSpace::FourWayStay => 5,
+            ActionSpace::EightWay => 9,
+        }
+    }
+
+    pub fn sample(&self) -> u8 {
+        gen_range(0, self.num_actions())
+    }
 }
@@ -0,0 +1,139 @@
+use std::fs;
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::enemy::CollisionKind;
+use crate::game::Game;
+use crate::level::LevelInfo;
+use crate::resources::init_resources_headless;
+use crate::*;
+
+/// Text view of a room
+/// `#` wall, `~` ice, `D` door, `K` key, `^` spike, `_` retracted spike, `C` crab,
+/// `c` chaser, `T` turret, `*` projectile, `@` agent, `x` dead agent
+pub struct AsciiGrid {
+    size: (usize, usize),
+    cells: Vec<char>,
+}
+
+impl AsciiGrid {
+    pub fn from_level(lvl: &LevelInfo) -> Self {
+        let mut grid = Self::tiles(lvl);
+        grid.set(lvl.door, 'D');
+        grid.set(lvl.key, 'K');
+        let items = lvl
+            .spikes
+            .iter()
+            .map(|e| (e, "spike"))
+            .chain(lvl.enemies.iter().map(|e| (e, "crab")));
+        for (item, layer_default) in items {
+            // Same kinds as the enemy manager, drawn as `from_game` draws them
+            let c = match item.property("enemy").unwrap_or(layer_default) {
+                "crab" => 'C',
+                "chaser" => 'c',
+                "turret" => 'T',
+                _ => '^',
+            };
+            grid.set(item.pos, c);
+        }
+        grid.set(lvl.agent, '@');
+
+        grid
+    }
+
+    pub fn from_game(game: &Game) -> Self {
+        let lvl = &game.lvl;
+        let mut grid = Self::tiles(lvl);
+        if !game.is_key_collected {
+            grid.set(lvl.door, 'D');
+            grid.set(lvl.key, 'K');
+        }
+        for (pos, kind, is_deadly) in game.enemy_positions() {
+            let c = match kind {
+                CollisionKind::Spike if !is_deadly => '_',
+                CollisionKind::Spike => '^',
+                CollisionKind::Crab => 'C',
+                CollisionKind::Chaser => 'c',
+                CollisionKind::Turret => 'T',
+                CollisionKind::Projectile => '*',
+            };
+            grid.set(pos, c);
+        }
+        let agent = match game.is_dead {
+            true => 'x',
+            false => '@',
+        };
+        grid.set(*game.get_current_agent_pos(), agent);
+
+        grid
+    }
+
+    /// Walls and ice of the room, everything else is floor
+    fn tiles(lvl: &LevelInfo) -> Self {
+        let mut grid = Self {
+            size: lvl.size,
+            cells: vec!['.'; lvl.size.0 * lvl.size.1],
+        };
+
+        for x in 0..lvl.size.0 {
+            for y in 0..lvl.size.1 {
+                if lvl.is_wall((x, y)) {
+                    grid.set((x, y), '#');
+                } else if lvl.is_ice((x, y)) {
+                    grid.set((x, y), '~');
+                }
+            }
+        }
+
+        grid
+    }
+
+    fn set(&mut self, (x, y): (usize, usize), c: char) {
+        if x < self.size.0 && y < self.size.1 {
+            self.cells[y * self.size.0 + x] = c;
+        }
+    }
+}
+
+impl std::fmt::Display for AsciiGrid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.cells.chunks(self.size.0) {
+            writeln!(f, "{}", row.iter().collect::<String>())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Plays back a genome (a file of moves, as saved by the recorder) in the
+/// terminal, a random genome is played if no file is given
+pub fn play(genome_path: Option<&str>, delay_ms: u64) {
+    init_resources_headless();
+
+    let mut game = match genome_path {
+        Some(path) => {
+            let genome = fs::read_to_string(path).expect("Failed to read the genome file");
+            Game::with_moves(&Game::parse_moves(&genome))
+        }
+        None => Game::new(),
+    };
+
+    let mut frame_count = 0;
+    while frame_count < NUM_FRAMES && !game.is_complete && !game.is_dead {
+        print_frame(&game, frame_count);
+        game.update(frame_count);
+        frame_count += 1;
+        sleep(Duration::from_millis(delay_ms));
+    }
+    print_frame(&game, frame_count);
+}
+
+fn print_frame(game: &Game, frame_count: usize) {
+    // Clear the terminal and move the cursor to the top left
+    print!("\x1b[2J\x1b[H");
+    print!("{}", AsciiGrid::from_game(game));
+    println!(
+        "Frame: {}/{}  Key: {}  Dead: {}  Complete: {}",
+        frame_count, NUM_FRAMES, game.is_key_collected, game.is_dead, game.is_complete
+    );
+}
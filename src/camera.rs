@@ -1,6 +1,9 @@
+use std::fs;
+
 use macroquad::prelude::*;
 
 use crate::configs::*;
+use crate::input::{Action, InputMap};
 
 pub struct MouseCam {
     offset: Vec2,
@@ -8,12 +11,15 @@ pub struct MouseCam {
 
     last_mouse_pos: Vec2,
     initial_offset: Vec2,
+    // World position and scale a smooth transition is heading to
+    goal: Option<(Vec2, f32)>,
+    // Changed since the state was last saved
+    is_moved: bool,
 }
 
 impl MouseCam {
     /// Mostly copied from
     /// https://github.com/not-fl3/macroquad/blob/master/src/experimental/camera/mouse.rs
-
     pub fn new(offset: Vec2, scale: f32) -> Self {
         Self {
             offset,
@@ -21,7 +27,42 @@ impl MouseCam {
 
             last_mouse_pos: Vec2::ZERO,
             initial_offset: offset,
+            goal: None,
+            is_moved: false,
+        }
+    }
+
+    /// Camera of the last session, see `save`. `None` if the file is
+    /// missing or broken, a zero or negative zoom included
+    pub fn load(path: &str, initial_offset: Vec2) -> Option<Self> {
+        let state = fs::read_to_string(path).ok()?;
+        let values: Vec<f32> = state
+            .split_whitespace()
+            .filter_map(|v| v.parse().ok())
+            .collect();
+        let [x, y, scale] = values[..] else {
+            return None;
+        };
+        if !values.iter().all(|v| v.is_finite()) || scale <= 0.0 {
+            return None;
         }
+
+        let mut cam = MouseCam::new(vec2(x, y), scale);
+        cam.initial_offset = initial_offset;
+        Some(cam)
+    }
+
+    /// Writes the position and zoom as `offset_x offset_y scale`
+    pub fn save(&mut self, path: &str) {
+        let state = format!("{} {} {}", self.offset.x, self.offset.y, self.scale);
+        if let Err(e) = fs::write(path, state) {
+            eprintln!("Failed to save the camera to {}: {}", path, e);
+        }
+        self.is_moved = false;
+    }
+
+    pub fn is_moved(&self) -> bool {
+        self.is_moved
     }
 
     pub fn get_cam(&self) -> Camera2D {
@@ -37,9 +78,58 @@ impl MouseCam {
         }
     }
 
+    /// Area of the world currently on screen
+    pub fn world_rect(&self) -> Rect {
+        let cam = self.get_cam();
+        let top_left = cam.screen_to_world(Vec2::ZERO);
+        let bottom_right = cam.screen_to_world(vec2(screen_width(), screen_height()));
+        let min = top_left.min(bottom_right);
+        let size = (bottom_right - top_left).abs();
+
+        Rect::new(min.x, min.y, size.x, size.y)
+    }
+
+    /// Moves the camera so the world position is in the middle of the screen
+    pub fn center_on(&mut self, pos: Vec2) {
+        let aspect = screen_width() / screen_height();
+        let target = vec2(screen_width() / 2.0, screen_height() / 2.0);
+        self.offset = vec2(
+            -self.scale * (pos.x - target.x),
+            -self.scale * aspect * (pos.y - target.y),
+        );
+    }
+
+    /// World position in the middle of the screen
+    pub fn center(&self) -> Vec2 {
+        let aspect = screen_width() / screen_height();
+        let target = vec2(screen_width() / 2.0, screen_height() / 2.0);
+        vec2(
+            target.x - self.offset.x / self.scale,
+            target.y - self.offset.y / (self.scale * aspect),
+        )
+    }
+
+    /// Smoothly moves to centre the world position, keeping the zoom
+    pub fn follow(&mut self, pos: Vec2) {
+        let scale = self.goal.map(|(_, s)| s).unwrap_or(self.scale);
+        self.goal = Some((pos, scale));
+    }
+
+    /// Smoothly moves and zooms so the area fills most of the screen
+    pub fn focus(&mut self, area: Rect) {
+        let visible = self.world_rect();
+        let fit = (visible.w / area.w).min(visible.h / area.h);
+        self.goal = Some((area.center(), self.scale * fit * 0.8));
+    }
+
+    pub fn mouse_world_pos(&self) -> Vec2 {
+        self.get_cam().screen_to_world(mouse_position().into())
+    }
+
     // To be called every frame.
     // To be called after wheel and pan update to take effect
-    pub fn update(&mut self, mouse_pos: Vec2, should_offset: bool) {
+    pub fn update(&mut self, mouse_pos: Vec2, should_offset: bool, input: &InputMap) {
+        let (offset, scale) = (self.offset, self.scale);
         let is_fast_zoom = is_key_down(KeyCode::LeftControl);
         let scale_factor = match is_fast_zoom {
             true => 1.5,
@@ -55,12 +145,20 @@ impl MouseCam {
         if is_mouse_button_down(MouseButton::Right) {
             self.pan_update();
         }
+        self.keyboard_update(input);
 
         if is_mouse_button_down(MouseButton::Middle) {
             self.offset = self.initial_offset;
             self.scale = INITIAL_CAMERA_SCALE;
         }
 
+        // Any manual move cancels a transition
+        if self.offset != offset || self.scale != scale {
+            self.goal = None;
+        }
+        self.transition_update();
+        self.is_moved |= self.offset != offset || self.scale != scale;
+
         set_camera(&self.get_cam());
         if should_offset {
             self.offset += mouse_pos - self.last_mouse_pos;
@@ -76,8 +174,56 @@ impl MouseCam {
         }
     }
 
+    /// Arrow keys pan, `=` and `-` zoom around the middle of the screen
+    fn keyboard_update(&mut self, input: &InputMap) {
+        let dt = get_frame_time();
+        let mut dir = Vec2::ZERO;
+        if input.is_down(Action::PanLeft) {
+            dir.x -= 1.0;
+        }
+        if input.is_down(Action::PanRight) {
+            dir.x += 1.0;
+        }
+        if input.is_down(Action::PanUp) {
+            dir.y -= 1.0;
+        }
+        if input.is_down(Action::PanDown) {
+            dir.y += 1.0;
+        }
+        // Screen space is 2 units across
+        self.offset -= dir * 2.0 * CAMERA_PAN_SPEED * dt;
+
+        if input.is_down(Action::ZoomIn) {
+            self.scale_mul(Vec2::ZERO, 2f32.powf(dt));
+        }
+        if input.is_down(Action::ZoomOut) {
+            self.scale_mul(Vec2::ZERO, 0.5f32.powf(dt));
+        }
+    }
+
+    /// Eases towards the goal, most of the way in `CAMERA_SMOOTHING` seconds
+    fn transition_update(&mut self) {
+        let Some((center, scale)) = self.goal else {
+            return;
+        };
+
+        let t = 1.0 - (-get_frame_time() / CAMERA_SMOOTHING).exp();
+        let new_center = self.center().lerp(center, t);
+        self.scale = (self.scale.ln() + (scale.ln() - self.scale.ln()) * t).exp();
+        self.center_on(new_center);
+
+        // Close enough to snap, less than a pixel away
+        let is_done = (new_center - center).length() * self.scale * screen_width() / 2.0 < 1.0
+            && (scale / self.scale - 1.0).abs() < 0.001;
+        if is_done {
+            self.scale = scale;
+            self.center_on(center);
+            self.goal = None;
+        }
+    }
+
     pub fn pan_update(&mut self) {
-        let current_mouse_pos: Vec2 = mouse_position_local().into();
+        let current_mouse_pos: Vec2 = mouse_position_local();
         let mouse_delta = current_mouse_pos - self.last_mouse_pos;
         self.last_mouse_pos = current_mouse_pos;
         self.offset += mouse_delta;
@@ -1,34 +1,147 @@
+use macroquad::prelude::KeyCode;
+
+use crate::agent::ActionSpace;
+use crate::input::Action;
+
 /// Window
 pub const WINDOW_BACKGROUND_COLOR: (u8, u8, u8, u8) = (36, 36, 36, 1);
 pub const INITIAL_CAMERA_SCALE: f32 = 0.00106;
 pub const IS_FULL_SCREEN: bool = true;
 pub const WINDOW_WIDTH: u32 = 1920;
 pub const WINDOW_HEIGHT: u32 = 1080;
+// Rooms narrower than this on screen (in px) are drawn in low detail in the multi view
+pub const MULTI_VIEW_LOD_WIDTH: f32 = 60.0;
+// Longer side of the minimap in px, shown when the room doesn't fit on screen
+pub const MINIMAP_SIZE: f32 = 260.0;
+// Seconds for the camera to get most of the way when following or focusing
+pub const CAMERA_SMOOTHING: f32 = 0.15;
+// Keyboard pan speed, in screens per second
+pub const CAMERA_PAN_SPEED: f32 = 1.0;
+// Where the camera position and zoom are kept between sessions
+pub const CAMERA_STATE_PATH: &str = "camera.txt";
+
+/// Input
+// Default keys of each action, any of them can be rebound in the help overlay
+// or in `INPUT_BINDINGS_PATH`, with one `action = Key, Key` line per action
+pub const KEY_BINDINGS: &[(Action, &[KeyCode])] = &[
+    (Action::Pause, &[KeyCode::Space]),
+    (Action::ToggleUi, &[KeyCode::Tab]),
+    (Action::Restart, &[KeyCode::R]),
+    (Action::SlowMode, &[KeyCode::Backspace]),
+    (Action::ToggleAi, &[KeyCode::Backslash]),
+    (Action::FrameSkip, &[KeyCode::RightShift]),
+    (Action::EditMode, &[KeyCode::L]),
+    (Action::FollowAgent, &[KeyCode::F]),
+    (Action::PlayMode, &[KeyCode::P]),
+    (Action::UseDemo, &[KeyCode::Enter]),
+    (Action::StepFrame, &[KeyCode::Period]),
+    (Action::StepGeneration, &[KeyCode::G]),
+    (Action::Help, &[KeyCode::H, KeyCode::F1]),
+    (Action::Quit, &[KeyCode::Escape]),
+    (Action::PanLeft, &[KeyCode::Left]),
+    (Action::PanRight, &[KeyCode::Right]),
+    (Action::PanUp, &[KeyCode::Up]),
+    (Action::PanDown, &[KeyCode::Down]),
+    (Action::ZoomIn, &[KeyCode::Equal, KeyCode::KpAdd]),
+    (Action::ZoomOut, &[KeyCode::Minus, KeyCode::KpSubtract]),
+    (Action::MoveUp, &[KeyCode::W]),
+    (Action::MoveLeft, &[KeyCode::A]),
+    (Action::MoveDown, &[KeyCode::S]),
+    (Action::MoveRight, &[KeyCode::D]),
+    (Action::Wait, &[KeyCode::X]),
+    (Action::MoveUpLeft, &[KeyCode::Q]),
+    (Action::MoveUpRight, &[KeyCode::E]),
+    (Action::MoveDownLeft, &[KeyCode::Z]),
+    (Action::MoveDownRight, &[KeyCode::C]),
+];
+pub const INPUT_BINDINGS_PATH: &str = "bindings.txt";
 
 /// Agent & Game
 pub const IS_PLAY_SLEEP_ANIMATION: bool = false;
+// Default moves, overridden by `--actions`: FourWay, FourWayStay (adds a wait
+// action, useful to time crabs) or EightWay
+pub const ACTION_SPACE: ActionSpace = ActionSpace::FourWay;
 pub const MUTATION_PROBABILITY: f32 = 10.0;
 pub const FF_WEIGHT_THRESHOLD: f32 = 100000.0;
 pub const POP_RETENTION_RATE: f32 = 5.0;
 pub const POP_EXPO_PERCENTAGE: f32 = 10.0;
+// Percentage of the children with a game pinned in the inspector as a parent
+pub const PINNED_PARENT_PERCENTAGE: f32 = 20.0;
+// Percentage of the children bred from the demonstrations played by hand,
+// each demo is also kept once as is
+pub const DEMO_CHILDREN_PERCENTAGE: f32 = 30.0;
+// Where demonstrations are saved, one genome file each
+pub const DEMO_DIR: &str = "demos";
+// Percentage of fitness lost when the agent dies, keep it below 100
+pub const DEATH_PENALTY: f32 = 50.0;
+// Kill the agent when it swaps tiles with a crab or a crab walks over it,
+// disable to compare against runs trained with the old end position only check
+pub const IS_SWEPT_COLLISION: bool = true;
+
+/// Stochastic environment
+// Percentage of steps where the agent's move is replaced by a random one
+pub const ACTION_NOISE: f32 = 0.0;
+// Start crabs, timed spikes and turrets at a random point in their cycle
+pub const IS_RANDOM_ENEMY_PHASE: bool = false;
+// Episodes played per genome, fitness is the average over all of them
+pub const NUM_ROLLOUTS: usize = 1;
+// Sample the agent spawn and the key from their candidate tiles every episode
+pub const IS_RANDOM_SPAWN: bool = false;
+pub const IS_RANDOM_KEY: bool = false;
 
 /// Simulation
 pub const NUM_FRAMES: usize = 200;
 pub const NUM_GAMES_IN_ROW: u32 = 35;
+pub const MULTI_VIEW_PADDING: f32 = 40.0;
 pub const NUM_GAMES: u32 = 1020;
 pub const UNIT_FRAME_SIZE: f32 = 8.0;
 pub const FRAME_SCALE: f32 = 10.0;
 
+/// Simulation speed
+// Steps (frames of every game) per second, set with the `Steps/s` slider
+pub const DEFAULT_STEPS_PER_SECOND: f32 = 60.0;
+// The slider's top end runs as many steps as fit in the time budget
+pub const MAX_STEPS_PER_SECOND: f32 = 20000.0;
+pub const SLOW_MODE_STEPS_PER_SECOND: f32 = 5.0;
+pub const FRAME_SKIP_SPEED_UP: f32 = 10.0;
+// Seconds of each rendered frame spent stepping the simulation, at most
+pub const SIMULATION_TIME_BUDGET: f32 = 0.012;
+
+/// Room generator
+pub const GENERATOR_SIZE: (usize, usize) = (25, 17);
+pub const GENERATOR_DIFFICULTY: f32 = 0.5;
+// Where the level editor saves rooms, `.json` and `.png` are added
+pub const EDITED_LEVEL_PATH: &str = "tiled/edited";
+
+/// Recorder
+// Output pixels per level pixel
+pub const RECORD_SCALE: u32 = 4;
+// Number of rooms per row (and column) when recording the grid view
+pub const RECORD_GRID_SIZE: usize = 6;
+
 /// Resources
 pub const TILESET_PATH: &str = "tiled/tileset.png";
 pub const TILE_SET_NAME: &str = "tileset.png";
 
+/// Hot reload
+// Reload the level when its files change on disk
+pub const IS_HOT_RELOAD: bool = true;
+// Seconds between checks of the level files
+pub const HOT_RELOAD_INTERVAL: f32 = 0.5;
+
 /// Levels
 pub const LVL_BACKGROUND_SPRITE: &str = "tiled/lvl2.png";
 pub const LVL_MAP_PATH: &str = "tiled/lvl2.json";
+// More levels trained in the same batch, as (map, background) pairs. Rooms can
+// be of any size, the games are split evenly between all the levels
+pub const LVL_EXTRA_LEVELS: &[(&str, &str)] = &[];
+pub const LAYER_BACKGROUND: &str = "background";
 pub const LAYER_WALLS: &str = "walls";
 pub const LAYER_DOOR: &str = "door";
 pub const LAYER_KEYS: &str = "keys";
 pub const LAYER_PLAYER: &str = "player";
 pub const LAYER_SPIKES: &str = "spikes";
 pub const LAYER_ENEMIES: &str = "enemies";
+pub const LAYER_ICE: &str = "ice";
+pub const LAYER_SPAWNS: &str = "spawns";
+pub const LAYER_KEY_SPAWNS: &str = "key_spawns";
@@ -0,0 +1,88 @@
+use std::fs;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::agent::{ActionSpace, AgentCommand};
+use crate::game::Game;
+use crate::input::InputMap;
+use crate::*;
+
+/// A run of a level played by hand. Every move is recorded, so the run can
+/// be fed to the population as a demonstration
+pub struct Demo {
+    game: Game,
+    frame_count: usize,
+}
+
+impl Demo {
+    pub fn on_level(level: usize) -> Self {
+        Demo::with_action_space(level, ACTION_SPACE)
+    }
+
+    pub fn with_action_space(level: usize, action_space: ActionSpace) -> Self {
+        let mut game = Game::on_level_with_moves(level, &[]);
+        game.action_space = action_space;
+        Self {
+            game,
+            frame_count: 0,
+        }
+    }
+
+    /// Steps the game on the move keys, WASD by default
+    pub fn handle_user_input(&mut self, input: &InputMap) {
+        if let Some(command) = input.pressed_command() {
+            self.play(command);
+        }
+    }
+
+    /// Plays one move, moves outside of the action space are ignored as the
+    /// agents couldn't learn them. Returns whether the move was played
+    pub fn play(&mut self, command: AgentCommand) -> bool {
+        if self.is_over() || command.to_int() >= self.game.action_space.num_actions() {
+            return false;
+        }
+
+        self.game.play_move(self.frame_count, command);
+        self.frame_count += 1;
+        true
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.game.is_dead || self.game.is_complete || self.frame_count >= NUM_FRAMES
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn level(&self) -> usize {
+        self.game.level
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// Moves played so far, followed by random ones to fill the episode
+    pub fn genome(&self) -> Vec<u8> {
+        let mut moves = self.game.moves().to_vec();
+        moves.resize_with(NUM_FRAMES, || self.game.action_space.sample());
+
+        moves
+    }
+
+    /// Writes the genome to a new file in the directory, in the format the
+    /// `play` and `eval` tools read (see `Game::parse_moves`). Returns the
+    /// file path
+    pub fn save(&self, dir: &str) -> io::Result<String> {
+        fs::create_dir_all(dir)?;
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let path = format!("{}/level{}_{}.txt", dir, self.level(), time);
+        fs::write(&path, Game::moves_to_string(&self.genome()))?;
+
+        Ok(path)
+    }
+}
@@ -1,38 +1,188 @@
+use std::io;
+use std::time::{Duration, Instant};
+
 use egui_macroquad::egui;
 use macroquad::prelude::*;
 
-use crate::{camera::MouseCam, simulation::SimulationStats, INITIAL_CAMERA_SCALE};
+use crate::agent::{ActionSpace, AgentCommand};
+use crate::demo::Demo;
+use crate::generator;
+use crate::input::{Action, InputMap};
+use crate::level_editor::{EditTool, LevelEditor};
+use crate::simulation::{GameInspection, Halt, StepTarget};
+use crate::{
+    camera::MouseCam, simulation::SimulationStats, ACTION_SPACE, CAMERA_STATE_PATH,
+    DEFAULT_STEPS_PER_SECOND, EDITED_LEVEL_PATH, FRAME_SKIP_SPEED_UP, INITIAL_CAMERA_SCALE,
+    INPUT_BINDINGS_PATH, MAX_STEPS_PER_SECOND, NUM_FRAMES, RESOURCES, SLOW_MODE_STEPS_PER_SECOND,
+};
+
+/// Which flow field, if any, is drawn over the room
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FlowFieldView {
+    Hidden,
+    Key,
+    Door,
+}
 
 pub struct Settings {
     pub is_pause: bool,
+    // Runs until the target is reached, then pauses
+    pub step_target: Option<StepTarget>,
+    pub target_generation: u32,
+    // Pause the first time an agent gets the key, since the last restart
+    pub is_break_on_key: bool,
+    pub is_break_on_best_fitness: bool,
+    // Why the simulation last paused itself
+    pub last_halt: Option<Halt>,
     pub is_draw: bool,
     pub is_restart: bool,
     pub is_frame_skip: bool,
     pub is_random_ai: bool,
     pub is_show_egui: bool,
+    pub is_show_help: bool,
+    pub is_quit: bool,
     pub is_ai_enabled: bool,
+    // Moves the agents pick from, changing it restarts the simulation
+    pub action_space: ActionSpace,
     pub is_show_multiple: bool,
+    pub is_show_death_map: bool,
+    pub is_show_visit_map: bool,
+    pub is_cumulative_visit_map: bool,
+    pub flow_field_view: FlowFieldView,
+    pub is_show_ff_values: bool,
+    pub num_ghosts: usize,
+    pub is_show_all_dots: bool,
+    // Keep the agent of the single view in the middle of the screen
+    pub is_follow_agent: bool,
+    pub is_show_minimap: bool,
+    // Level shown in the single view, when training on more than one
+    pub selected_level: usize,
+    // Game (counted across all levels) to zoom in on in the multi view
+    pub focus_game: usize,
+    pub is_focus_game: bool,
+    // Game shown in the inspector, picked by clicking it in the multi view.
+    // Only for the current generation, the indices point at new games after
+    pub inspected_game: Option<usize>,
+    pub is_replay_game: bool,
+    pub is_stop_replay: bool,
+    pub is_pin_game: bool,
+    // Play the level by hand, the training waits meanwhile
+    pub is_play_mode: bool,
+    pub is_use_demo: bool,
+    pub is_retry_demo: bool,
+    pub is_edit_mode: bool,
+    pub is_train_on_room: bool,
+    // On a hot reload, keep training the genomes instead of restarting
+    pub is_keep_genomes_on_reload: bool,
     pub slow_mode: bool,
+    // Speed of the simulation, see `target_steps_per_second`
+    pub steps_per_second: f32,
 }
 
 pub struct Editor {
     pub settings: Settings,
+    // Created the first time edit mode is turned on
+    pub level_editor: Option<LevelEditor>,
+    // Why the active level couldn't be opened in the level editor
+    level_editor_error: Option<String>,
+    pub input: InputMap,
+    // Outcome of saving the last demo used, path of the file on success
+    pub last_demo_save: Option<io::Result<String>>,
+    // Action waiting for a key press in the help overlay
+    rebinding: Option<Action>,
+    // Key of the last rebind that another action has already
+    rebind_conflict: Option<(KeyCode, Action)>,
     mouse_cam: MouseCam,
+    is_pointer_over_ui: bool,
+    // World position the camera follows, see `is_follow_agent`
+    follow_target: Option<Vec2>,
+    // World position of a left click on the simulation this frame
+    click_pos: Option<Vec2>,
+    last_camera_save: Instant,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Settings {
     pub fn new() -> Self {
         Self {
             is_pause: false,
+            step_target: None,
+            target_generation: 10,
+            is_break_on_key: false,
+            is_break_on_best_fitness: false,
+            last_halt: None,
             is_draw: true,
             is_restart: false,
             is_frame_skip: false,
             is_ai_enabled: true,
+            action_space: ACTION_SPACE,
             is_show_egui: false,
+            is_show_help: false,
+            is_quit: false,
             is_show_multiple: false,
+            is_show_death_map: false,
+            is_show_visit_map: false,
+            is_cumulative_visit_map: false,
+            flow_field_view: FlowFieldView::Hidden,
+            is_show_ff_values: false,
+            num_ghosts: 0,
+            is_show_all_dots: false,
+            is_follow_agent: false,
+            is_show_minimap: true,
+            selected_level: 0,
+            focus_game: 0,
+            is_focus_game: false,
+            inspected_game: None,
+            is_replay_game: false,
+            is_stop_replay: false,
+            is_pin_game: false,
+            is_play_mode: false,
+            is_use_demo: false,
+            is_retry_demo: false,
+            is_edit_mode: false,
+            is_train_on_room: false,
+            is_keep_genomes_on_reload: true,
             is_random_ai: false,
             slow_mode: false,
+            steps_per_second: DEFAULT_STEPS_PER_SECOND,
+        }
+    }
+
+    /// Runs the simulation until the target, it pauses there. Ignored for
+    /// targets the simulation can't reach with the AI disabled
+    pub fn run_until(&mut self, target: StepTarget) {
+        if target.is_needing_ai() && !self.is_ai_enabled {
+            return;
         }
+        self.step_target = Some(target);
+        self.last_halt = None;
+        self.is_pause = false;
+    }
+
+    /// Rate the scheduler aims for, infinite runs as fast as it can. Slow
+    /// mode and frame skip override the slider, as does turning drawing off
+    pub fn target_steps_per_second(&self) -> f32 {
+        if self.slow_mode {
+            return SLOW_MODE_STEPS_PER_SECOND;
+        }
+        if !self.is_draw || self.steps_per_second >= MAX_STEPS_PER_SECOND {
+            return f32::INFINITY;
+        }
+        match self.is_frame_skip {
+            true => self.steps_per_second * FRAME_SKIP_SPEED_UP,
+            false => self.steps_per_second,
+        }
+    }
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -40,41 +190,201 @@ impl Editor {
     pub fn new() -> Self {
         Self {
             settings: Settings::new(),
-            mouse_cam: MouseCam::new(vec2(0.25, 0.04), INITIAL_CAMERA_SCALE),
+            level_editor: None,
+            level_editor_error: None,
+            last_demo_save: None,
+            input: InputMap::load(INPUT_BINDINGS_PATH),
+            rebinding: None,
+            rebind_conflict: None,
+            mouse_cam: MouseCam::load(CAMERA_STATE_PATH, vec2(0.25, 0.04))
+                .unwrap_or_else(|| MouseCam::new(vec2(0.25, 0.04), INITIAL_CAMERA_SCALE)),
+            is_pointer_over_ui: false,
+            follow_target: None,
+            click_pos: None,
+            last_camera_save: Instant::now(),
         }
     }
 
+    pub fn visible_rect(&self) -> Rect {
+        self.mouse_cam.world_rect()
+    }
+
+    pub fn camera(&self) -> Camera2D {
+        self.mouse_cam.get_cam()
+    }
+
+    pub fn set_follow_target(&mut self, target: Option<Vec2>) {
+        self.follow_target = target;
+    }
+
+    /// Smoothly zooms in on an area of the world, like a game of the multi view
+    pub fn focus(&mut self, area: Rect) {
+        self.settings.is_follow_agent = false;
+        self.mouse_cam.focus(area);
+    }
+
+    /// Keys go to the help overlay while it waits for a new binding
+    pub fn is_rebinding(&self) -> bool {
+        self.rebinding.is_some()
+    }
+
+    /// Where the simulation was left clicked this frame, if it was
+    pub fn take_click(&mut self) -> Option<Vec2> {
+        self.click_pos.take()
+    }
+
+    pub fn save_camera(&mut self) {
+        self.mouse_cam.save(CAMERA_STATE_PATH);
+        self.last_camera_save = Instant::now();
+    }
+
     pub fn update(&mut self) {
         // Camera update
-        self.mouse_cam.update(mouse_position_local(), false);
+        if let Some(target) = self.follow_target.filter(|_| self.settings.is_follow_agent) {
+            self.mouse_cam.follow(target);
+        }
+        self.mouse_cam
+            .update(mouse_position_local(), false, &self.input);
+        let is_save_due = self.last_camera_save.elapsed() > Duration::from_secs(1);
+        if self.mouse_cam.is_moved() && is_save_due {
+            self.save_camera();
+        }
+
+        // Clicks in edit mode paint the room instead
+        let is_click = is_mouse_button_pressed(MouseButton::Left)
+            && !self.is_pointer_over_ui
+            && !self.settings.is_edit_mode;
+        self.click_pos = match is_click {
+            true => Some(self.mouse_cam.mouse_world_pos()),
+            false => None,
+        };
+
+        // The next key pressed is bound to the action picked in the help
+        if let Some(action) = self.rebinding {
+            match get_last_key_pressed() {
+                Some(KeyCode::Escape) => self.rebinding = None,
+                Some(key) => {
+                    match self.input.bind(action, key) {
+                        Ok(()) => self.input.save(INPUT_BINDINGS_PATH),
+                        Err(other) => self.rebind_conflict = Some((key, other)),
+                    }
+                    self.rebinding = None;
+                }
+                None => (),
+            }
+            return;
+        }
 
         // Handle keyboard input
-        if is_key_pressed(KeyCode::Space) {
+        self.settings.is_quit = self.input.is_pressed(Action::Quit);
+        if self.input.is_pressed(Action::Pause) {
             self.settings.is_pause = !self.settings.is_pause;
+            self.settings.last_halt = None;
         }
-        if is_key_pressed(KeyCode::Tab) {
+        if self.input.is_pressed(Action::ToggleUi) {
             self.settings.is_show_egui = !self.settings.is_show_egui;
         }
-        if is_key_pressed(KeyCode::R) {
+        if self.input.is_pressed(Action::Help) {
+            self.settings.is_show_help = !self.settings.is_show_help;
+        }
+        if self.input.is_pressed(Action::Restart) {
             self.settings.is_restart = true;
         }
-        if is_key_pressed(KeyCode::Backspace) {
+        if self.input.is_pressed(Action::SlowMode) {
             self.settings.slow_mode = !self.settings.slow_mode;
         }
-        if is_key_pressed(KeyCode::Backslash) {
+        if self.input.is_pressed(Action::ToggleAi) {
             self.settings.is_ai_enabled = !self.settings.is_ai_enabled;
         }
-        if is_key_pressed(KeyCode::RightShift) {
+        // Disabling the AI cancels targets it can't reach anymore
+        let settings = &mut self.settings;
+        if !settings.is_ai_enabled && settings.step_target.is_some_and(|t| t.is_needing_ai()) {
+            settings.step_target = None;
+        }
+        if self.input.is_pressed(Action::FrameSkip) {
             self.settings.is_frame_skip = !self.settings.is_frame_skip;
         }
+        if self.input.is_pressed(Action::EditMode) {
+            self.settings.is_edit_mode = !self.settings.is_edit_mode;
+        }
+        if self.input.is_pressed(Action::FollowAgent) {
+            self.settings.is_follow_agent = !self.settings.is_follow_agent;
+        }
+        if self.input.is_pressed(Action::PlayMode) {
+            self.settings.is_play_mode = !self.settings.is_play_mode;
+        }
+        if self.input.is_pressed(Action::StepFrame) {
+            self.settings.run_until(StepTarget::Frame);
+        }
+        if self.input.is_pressed(Action::StepGeneration) {
+            self.settings.run_until(StepTarget::Generation);
+        }
+        if self.input.is_pressed(Action::UseDemo) && self.settings.is_play_mode {
+            self.settings.is_use_demo = true;
+        }
+
+        // The level editor follows the level picked in the Level slider
+        let level = self.settings.selected_level.min(RESOURCES.num_levels() - 1);
+        if self.level_editor.as_ref().map(|e| e.level) != Some(level) {
+            self.level_editor = None;
+        }
+        if self.settings.is_edit_mode && self.level_editor.is_none() {
+            match LevelEditor::new(level) {
+                Ok(level_editor) => {
+                    self.level_editor = Some(level_editor);
+                    self.level_editor_error = None;
+                }
+                Err(e) => {
+                    self.level_editor_error = Some(e);
+                    self.settings.is_edit_mode = false;
+                }
+            }
+        }
+        if self.settings.is_edit_mode {
+            let mouse_pos = match self.is_pointer_over_ui {
+                true => None,
+                false => Some(self.mouse_cam.mouse_world_pos()),
+            };
+            if let Some(level_editor) = self.level_editor.as_mut() {
+                level_editor.update(mouse_pos);
+            }
+        }
     }
 
-    pub fn draw(&mut self, stats: &SimulationStats) {
-        if !self.settings.is_show_egui {
+    pub fn draw(
+        &mut self,
+        stats: &SimulationStats,
+        inspection: Option<GameInspection>,
+        demo: Option<&Demo>,
+    ) {
+        if self.settings.is_edit_mode {
+            if let Some(level_editor) = &self.level_editor {
+                level_editor.draw();
+            }
+        }
+
+        // The inspector, the play panel and the help stay open while the
+        // rest of the ui is hidden
+        self.is_pointer_over_ui = false;
+        let is_any_window = inspection.is_some() || demo.is_some() || self.settings.is_show_help;
+        if !self.settings.is_show_egui && !is_any_window {
             return;
         }
 
         egui_macroquad::ui(|ctx| {
+            self.is_pointer_over_ui = ctx.wants_pointer_input();
+            if let Some(inspection) = &inspection {
+                self.draw_inspector(ctx, inspection, stats.frame_count);
+            }
+            if let Some(demo) = demo {
+                self.draw_play_panel(ctx, demo, stats.num_queued_demos);
+            }
+            if self.settings.is_show_help {
+                self.draw_help(ctx);
+            }
+            if !self.settings.is_show_egui {
+                return;
+            }
             egui::Window::new("No Title")
                 .title_bar(false)
                 .min_width(200.0)
@@ -86,22 +396,172 @@ impl Editor {
                             ui.label(format!("FPS: {}", get_fps()));
                             ui.label(format!("Frame: {}", stats.frame_count));
                             ui.label(format!("Gen: {}", stats.generation_count));
+                            ui.label(format!("Steps/s: {:.0}", stats.steps_per_second));
+                            ui.label(format!("Deaths: {}", stats.num_deaths));
+                            if stats.num_queued_demos > 0 {
+                                ui.label(format!("Queued demos: {}", stats.num_queued_demos));
+                            }
                         });
 
                     egui::CollapsingHeader::new("Options")
                         .default_open(true)
                         .show(ui, |ui| {
                             ui.checkbox(&mut self.settings.is_draw, "Draw");
+                            ui.add(
+                                egui::Slider::new(
+                                    &mut self.settings.steps_per_second,
+                                    1.0..=MAX_STEPS_PER_SECOND,
+                                )
+                                .logarithmic(true)
+                                .custom_formatter(|v, _| match v as f32 >= MAX_STEPS_PER_SECOND {
+                                    true => "max".to_string(),
+                                    false => format!("{:.0}", v),
+                                })
+                                .text("Steps/s"),
+                            );
                             ui.checkbox(&mut self.settings.slow_mode, "Slow Mode");
                             ui.checkbox(&mut self.settings.is_show_multiple, "Show Multi");
                             ui.checkbox(&mut self.settings.is_ai_enabled, "Enable AI");
+                            ui.checkbox(&mut self.settings.is_play_mode, "Play Mode");
                             ui.checkbox(&mut self.settings.is_frame_skip, "Frame skip");
+                            ui.horizontal(|ui| {
+                                ui.label("Moves");
+                                for space in ActionSpace::ALL {
+                                    let label = space.num_actions().to_string();
+                                    let action_space = &mut self.settings.action_space;
+                                    if ui.radio_value(action_space, space, label).changed() {
+                                        self.settings.is_restart = true;
+                                    }
+                                }
+                            });
+                            let num_levels = RESOURCES.num_levels();
+                            if num_levels > 1 {
+                                ui.add(
+                                    egui::Slider::new(
+                                        &mut self.settings.selected_level,
+                                        0..=num_levels - 1,
+                                    )
+                                    .text("Level"),
+                                );
+                            }
+                            ui.checkbox(&mut self.settings.is_show_death_map, "Death Map");
+                            ui.checkbox(&mut self.settings.is_show_visit_map, "Visit Map");
+                            if self.settings.is_show_visit_map {
+                                ui.checkbox(
+                                    &mut self.settings.is_cumulative_visit_map,
+                                    "Cumulative",
+                                );
+                            }
+                        });
+
+                    egui::CollapsingHeader::new("Camera")
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            ui.checkbox(&mut self.settings.is_follow_agent, "Follow Agent");
+                            ui.checkbox(&mut self.settings.is_show_minimap, "Minimap");
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::DragValue::new(&mut self.settings.focus_game)
+                                        .prefix("Game #"),
+                                );
+                                if ui.button("Focus").clicked() {
+                                    self.settings.is_focus_game = true;
+                                }
+                            });
+                        });
+
+                    egui::CollapsingHeader::new("Debug")
+                        .default_open(false)
+                        .show(ui, |ui| self.draw_debug_controls(ui));
+
+                    egui::CollapsingHeader::new("Flow Field")
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            ui.horizontal(|ui| {
+                                let view = &mut self.settings.flow_field_view;
+                                ui.radio_value(view, FlowFieldView::Hidden, "Off");
+                                ui.radio_value(view, FlowFieldView::Key, "Key");
+                                ui.radio_value(view, FlowFieldView::Door, "Door");
+                            });
+                            ui.checkbox(&mut self.settings.is_show_ff_values, "Values");
+                        });
+
+                    egui::CollapsingHeader::new("Ghosts")
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            ui.add(
+                                egui::Slider::new(&mut self.settings.num_ghosts, 0..=50)
+                                    .text("Top N"),
+                            );
+                            ui.checkbox(&mut self.settings.is_show_all_dots, "All Agents");
+                        });
+
+                    egui::CollapsingHeader::new("Level Editor")
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            ui.checkbox(&mut self.settings.is_edit_mode, "Edit Mode");
+                            if let Some(error) = &self.level_editor_error {
+                                ui.colored_label(egui::Color32::RED, error);
+                            }
+                            let Some(level_editor) = self.level_editor.as_mut() else {
+                                return;
+                            };
+                            if !self.settings.is_edit_mode {
+                                return;
+                            }
+
+                            ui.horizontal_wrapped(|ui| {
+                                for tool in EditTool::ALL {
+                                    ui.radio_value(&mut level_editor.tool, tool, tool.name());
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                let undo = egui::Button::new("Undo");
+                                if ui.add_enabled(level_editor.can_undo(), undo).clicked() {
+                                    level_editor.undo();
+                                }
+                                let redo = egui::Button::new("Redo");
+                                if ui.add_enabled(level_editor.can_redo(), redo).clicked() {
+                                    level_editor.redo();
+                                }
+                            });
+                            ui.label(match level_editor.is_solvable {
+                                true => "Solvable",
+                                false => "Not solvable",
+                            });
+                            ui.horizontal(|ui| {
+                                let train = egui::Button::new("Train on this room");
+                                if ui.add_enabled(level_editor.is_solvable, train).clicked() {
+                                    self.settings.is_train_on_room = true;
+                                }
+                                if ui.button("Save").clicked() {
+                                    level_editor.last_save = Some(generator::save_room(
+                                        &level_editor.room,
+                                        EDITED_LEVEL_PATH,
+                                    ));
+                                }
+                            });
+                            match &level_editor.last_save {
+                                Some(Ok(())) => {
+                                    ui.label(format!("Saved to {}.json", EDITED_LEVEL_PATH));
+                                }
+                                Some(Err(e)) => {
+                                    let error = format!("Failed to save: {}", e);
+                                    ui.colored_label(egui::Color32::RED, error);
+                                }
+                                None => {}
+                            }
                         });
 
                     egui::CollapsingHeader::new("Controls")
                         .default_open(true)
                         .show(ui, |ui| {
                             ui.checkbox(&mut self.settings.is_pause, "Pause");
+                            ui.checkbox(&mut self.settings.is_show_help, "Key Bindings");
+                            ui.checkbox(
+                                &mut self.settings.is_keep_genomes_on_reload,
+                                "Keep genomes on reload",
+                            );
                             if ui.add(egui::Button::new("Restart")).clicked() {
                                 self.settings.is_restart = true;
                             }
@@ -110,4 +570,280 @@ impl Editor {
         });
         egui_macroquad::draw();
     }
+
+    fn draw_debug_controls(&mut self, ui: &mut egui::Ui) {
+        let settings = &mut self.settings;
+        ui.horizontal(|ui| {
+            ui.label("Step:");
+            for (name, target) in [
+                ("Frame", StepTarget::Frame),
+                ("Episode", StepTarget::Episode),
+                ("Generation", StepTarget::Generation),
+            ] {
+                let is_enabled = settings.is_ai_enabled || !target.is_needing_ai();
+                if ui
+                    .add_enabled(is_enabled, egui::Button::new(name))
+                    .clicked()
+                {
+                    settings.run_until(target);
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            let is_enabled = settings.is_ai_enabled;
+            let until_solved = egui::Button::new("Until solved");
+            if ui.add_enabled(is_enabled, until_solved).clicked() {
+                settings.run_until(StepTarget::Solved);
+            }
+            let until_gen = egui::Button::new("Until gen");
+            if ui.add_enabled(is_enabled, until_gen).clicked() {
+                settings.run_until(StepTarget::UntilGeneration(settings.target_generation));
+            }
+            ui.add(egui::DragValue::new(&mut settings.target_generation).clamp_range(1..=u32::MAX));
+        });
+
+        if !settings.is_ai_enabled {
+            ui.label("Enable the AI to step further than a frame");
+        }
+
+        ui.label("Pause when");
+        ui.checkbox(&mut settings.is_break_on_key, "An agent first gets the key");
+        ui.checkbox(
+            &mut settings.is_break_on_best_fitness,
+            "Best fitness improves",
+        );
+
+        if let Some(target) = settings.step_target {
+            ui.horizontal(|ui| {
+                ui.label(format!("Running until {:?}", target));
+                if ui.button("Cancel").clicked() {
+                    settings.step_target = None;
+                }
+            });
+        }
+        if let Some(halt) = settings.last_halt {
+            ui.label(format!("Paused: {}", halt.describe()));
+        }
+    }
+
+    /// Bindings of every action, click one to bind it to the next key pressed
+    fn draw_help(&mut self, ctx: &egui::Context) {
+        let mut is_open = true;
+
+        egui::Window::new("Controls")
+            .open(&mut is_open)
+            .default_pos(egui::pos2(screen_width() / 2.0 - 180.0, 40.0))
+            .show(ctx, |ui| {
+                ui.label("Click a binding, then press a key to add (Escape cancels)");
+                if let Some((key, other)) = self.rebind_conflict {
+                    let conflict = format!("{:?} is bound to {} already", key, other.description());
+                    ui.colored_label(egui::Color32::RED, conflict);
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(screen_height() * 0.6)
+                    .show(ui, |ui| {
+                        egui::Grid::new("bindings").striped(true).show(ui, |ui| {
+                            for action in Action::ALL {
+                                ui.label(action.description());
+                                let keys = match self.rebinding == Some(action) {
+                                    true => "Press a key...".to_string(),
+                                    false => self.input.keys_text(action),
+                                };
+                                if ui.button(keys).clicked() {
+                                    self.rebinding = Some(action);
+                                    self.rebind_conflict = None;
+                                }
+                                if ui.small_button("Clear").clicked() {
+                                    self.input.set_keys(action, Vec::new());
+                                    self.input.save(INPUT_BINDINGS_PATH);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+                ui.label("Mouse: wheel zooms, right drag pans, middle click resets the camera");
+                if ui.button("Reset to defaults").clicked() {
+                    self.input = InputMap::new();
+                    self.input.save(INPUT_BINDINGS_PATH);
+                    self.rebinding = None;
+                    self.rebind_conflict = None;
+                }
+            });
+
+        if !is_open {
+            self.settings.is_show_help = false;
+            self.rebinding = None;
+        }
+    }
+
+    fn draw_play_panel(&mut self, ctx: &egui::Context, demo: &Demo, num_queued: usize) {
+        let game = demo.game();
+        let mut is_open = true;
+
+        egui::Window::new("Play")
+            .open(&mut is_open)
+            .default_pos(egui::pos2(20.0, 20.0))
+            .default_width(260.0)
+            .show(ctx, |ui| {
+                let moves: Vec<String> = [
+                    Action::MoveUp,
+                    Action::MoveLeft,
+                    Action::MoveDown,
+                    Action::MoveRight,
+                    Action::Wait,
+                ]
+                .map(|a| self.input.keys_text(a))
+                .to_vec();
+                ui.label(format!(
+                    "{} to move, {} to wait, {} to use the run",
+                    moves[..4].join(" "),
+                    moves[4],
+                    self.input.keys_text(Action::UseDemo)
+                ));
+                let status = match (&game.death, game.is_complete) {
+                    (Some(d), _) => format!("Dead, {:?}", d.kind),
+                    (None, true) => "Escaped".to_string(),
+                    (None, false) if demo.is_over() => "Out of moves".to_string(),
+                    (None, false) if game.is_key_collected => "Has the key".to_string(),
+                    (None, false) => "Looking for the key".to_string(),
+                };
+                ui.label(format!("Status: {}", status));
+                ui.label(format!("Moves: {} / {}", demo.frame_count(), NUM_FRAMES));
+                if num_queued > 0 {
+                    ui.label(format!("Queued for the next generation: {}", num_queued));
+                }
+
+                ui.horizontal(|ui| {
+                    let use_demo = egui::Button::new("Use as demo");
+                    if ui
+                        .add_enabled(demo.frame_count() > 0, use_demo)
+                        .on_hover_text("Seeds the next generation with it and mutations of it")
+                        .clicked()
+                    {
+                        self.settings.is_use_demo = true;
+                    }
+                    if ui.button("Retry").clicked() {
+                        self.settings.is_retry_demo = true;
+                    }
+                });
+                match &self.last_demo_save {
+                    Some(Ok(path)) => {
+                        ui.label(format!("Saved the last demo to {}", path));
+                    }
+                    Some(Err(e)) => {
+                        let error = format!("Failed to save the demo: {}", e);
+                        ui.colored_label(egui::Color32::RED, error);
+                    }
+                    None => {}
+                }
+            });
+
+        if !is_open {
+            self.settings.is_play_mode = false;
+        }
+    }
+
+    fn draw_inspector(&mut self, ctx: &egui::Context, inspection: &GameInspection, frame: usize) {
+        let game = inspection.game;
+        let fitness = &inspection.fitness;
+        let mut is_open = true;
+
+        egui::Window::new(format!("Game #{}", inspection.idx))
+            .open(&mut is_open)
+            .default_pos(egui::pos2(screen_width() - 340.0, screen_height() - 520.0))
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                if RESOURCES.num_levels() > 1 {
+                    ui.label(format!("Level: {}", inspection.level));
+                }
+                let status = match (&game.death, game.is_complete) {
+                    (Some(d), _) => format!("Dead, {:?} at frame {}", d.kind, d.frame),
+                    (None, true) => "Escaped".to_string(),
+                    (None, false) if game.is_key_collected => "Has the key".to_string(),
+                    (None, false) => "Looking for the key".to_string(),
+                };
+                ui.label(format!("Status: {}", status));
+                let (key_steps, door_steps) = game.num_steps();
+                ui.label(format!(
+                    "Key steps: {}, door steps: {}",
+                    key_steps, door_steps
+                ));
+
+                egui::CollapsingHeader::new("Fitness")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        egui::Grid::new("fitness").show(ui, |ui| {
+                            let mut row = |name: &str, value: String| {
+                                ui.label(name);
+                                ui.label(value);
+                                ui.end_row();
+                            };
+                            row("Key distance", fitness.key_distance.to_string());
+                            row("Door distance", fitness.door_distance.to_string());
+                            row("Key", format!("{:.2}", fitness.key_score));
+                            row("Door", format!("{:.2}", fitness.door_score));
+                            row("Bonus", format!("{:.2}", fitness.bonus));
+                            row("Death penalty", format!("-{:.2}", fitness.death_penalty));
+                            row("Total", format!("{:.2}", fitness.total));
+                            row("Last generation", format!("{:.2}", game.fitness));
+                        });
+                    });
+
+                // Moves played so far are bright, the rest dimmed
+                egui::CollapsingHeader::new("Genome")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        let arrows: Vec<char> = game
+                            .moves()
+                            .iter()
+                            .map(|m| AgentCommand::from_int(*m).arrow())
+                            .collect();
+                        let played = frame.min(arrows.len());
+                        let mut job = egui::text::LayoutJob::default();
+                        for (text, color) in [
+                            (&arrows[..played], egui::Color32::WHITE),
+                            (&arrows[played..], egui::Color32::GRAY),
+                        ] {
+                            job.append(
+                                &text.iter().collect::<String>(),
+                                0.0,
+                                egui::TextFormat {
+                                    font_id: egui::FontId::monospace(12.0),
+                                    color,
+                                    ..Default::default()
+                                },
+                            );
+                        }
+                        egui::ScrollArea::vertical()
+                            .max_height(120.0)
+                            .show(ui, |ui| ui.label(job));
+                    });
+
+                ui.horizontal(|ui| {
+                    if ui.button("Replay solo").clicked() {
+                        self.settings.is_replay_game = true;
+                    }
+                    if inspection.is_replaying && ui.button("Stop replay").clicked() {
+                        self.settings.is_stop_replay = true;
+                    }
+                });
+                let pin = match inspection.is_pinned {
+                    true => "Unpin",
+                    false => "Pin as parent",
+                };
+                if ui
+                    .button(pin)
+                    .on_hover_text("Parents a share of the next generation's children")
+                    .clicked()
+                {
+                    self.settings.is_pin_game = true;
+                }
+            });
+
+        // Closing the inspector ends the replay too
+        if !is_open {
+            self.settings.inspected_game = None;
+            self.settings.is_stop_replay = true;
+        }
+    }
 }
@@ -1,20 +1,134 @@
+use std::collections::VecDeque;
 use std::f32::consts::PI;
+use std::sync::Arc;
 
 use macroquad::prelude::*;
+use macroquad::rand::gen_range;
 
-use crate::level::GameItem;
+use crate::level::{GameItem, LevelInfo};
+use crate::resources::Resources;
 use crate::*;
 
 #[derive(Clone)]
 enum EnemyKind {
     Spike(f32),
-    Crab(bool),
+    Crab(Patrol),
+    Chaser(Chase),
+    TimedSpike(Cycle),
+    Turret(Turret),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+/// Back and forth movement of a crab, configured with tile properties in Tiled
+/// - `axis`: `horizontal` (default) or `vertical`
+/// - `direction`: `positive` (default, right/down) or `negative` (left/up)
+/// - `speed`: tiles moved per frame, defaults to `1`
+/// - `min`, `max`: optional patrol bounds along the axis, in tiles
+/// - `path`: tiles to walk back and forth along instead, as `x,y;x,y;..`.
+///   Polylines on object layers are turned into this
+#[derive(Clone)]
+struct Patrol {
+    axis: Axis,
+    is_positive: bool,
+    speed: f32,
+    progress: f32,
+    bounds: (usize, usize),
+    // Every tile of the path, empty for axis patrols
+    path: Vec<(usize, usize)>,
+    path_idx: usize,
+}
+
+/// Walks towards the agent along the shortest path
+/// - `speed`: tiles moved per frame, defaults to `0.5`
+#[derive(Clone)]
+struct Chase {
+    speed: f32,
+    progress: f32,
+}
+
+/// Distance to the agent of every tile, shared by every chaser. It's only
+/// solved again once the agent has moved
+#[derive(Clone, Default)]
+struct ChaseField {
+    agent_pos: Option<(usize, usize)>,
+    dist: Vec<usize>,
+}
+
+/// Spike that retracts and extends, only deadly while extended
+/// - `period`: length of a cycle in frames, defaults to `20`
+/// - `active`: frames per cycle the spike is extended, defaults to `10`
+/// - `offset`: frames the cycle is shifted by, defaults to `0`
+#[derive(Clone)]
+struct Cycle {
+    period: usize,
+    active: usize,
+    offset: usize,
+    frame: usize,
+}
+
+/// Static turret firing projectiles in a straight line until they hit a wall
+/// - `direction`: `up`, `down`, `left` or `right` (default)
+/// - `period`: frames between shots, defaults to `10`
+#[derive(Clone)]
+struct Turret {
+    dir: (isize, isize),
+    period: usize,
+    frame: usize,
+    projectiles: Vec<(usize, usize)>,
+}
+
+/// What killed the agent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CollisionKind {
+    Spike,
+    Crab,
+    Chaser,
+    Turret,
+    Projectile,
+}
+
+/// Emitted by the enemy manager when the agent runs into an enemy
+#[derive(Clone, Debug)]
+pub struct Collision {
+    pub kind: CollisionKind,
+    /// Tile id of the enemy in the tileset
+    pub tile_id: u32,
+    pub pos: (usize, usize),
+    pub frame: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpriteKind {
+    Crab,
+    SmallSpike,
+    LargeSpike,
+    Turret,
+    Projectile,
+}
+
+/// Renderer agnostic description of how an enemy looks this frame
+#[derive(Clone, Debug)]
+pub struct EnemySprite {
+    pub pos: (usize, usize),
+    pub kind: SpriteKind,
+    pub rotation: f32,
+    pub tint: Color,
 }
 
 #[derive(Clone)]
 pub struct EnemyManager {
+    // Level the enemies walk, see `RESOURCES`
+    resources: Arc<Resources>,
     enemies: Vec<Enemy>,
     spikes: Vec<Enemy>,
+    chase_field: ChaseField,
+    // See `IS_SWEPT_COLLISION`
+    pub is_swept_collision: bool,
 }
 
 #[derive(Clone)]
@@ -25,41 +139,92 @@ struct Enemy {
 }
 
 impl EnemyManager {
+    /// Items on the enemies layer are crabs and items on the spikes layer
+    /// are spikes, unless the tile sets the `enemy` property to one of
+    /// `crab`, `chaser`, `spike`, `timed_spike` or `turret`
     pub fn new(enemies: Vec<GameItem>, spikes: Vec<GameItem>) -> Self {
-        Self {
-            enemies: enemies
-                .iter()
-                .map(|e| Enemy::new(e.pos.0, e.pos.1, EnemyKind::Crab(false), e))
-                .collect(),
-            spikes: spikes
-                .iter()
-                .map(|e| Enemy::new(e.pos.0, e.pos.1, EnemyKind::Spike(0.0), e))
-                .collect(),
-        }
+        EnemyManager::on_level(0, enemies, spikes)
     }
 
-    pub fn update(&mut self, agent_pos: &(usize, usize)) -> bool {
-        for e in self.enemies.iter_mut() {
-            if e.update(agent_pos) {
-                return true;
+    pub fn on_level(level: usize, enemies: Vec<GameItem>, spikes: Vec<GameItem>) -> Self {
+        let resources = RESOURCES.level(level).unwrap();
+        let lvl = &resources.lvl_info;
+        let (mut moving, mut stationary) = (Vec::new(), Vec::new());
+        let items = enemies
+            .iter()
+            .map(|e| (e, "crab"))
+            .chain(spikes.iter().map(|e| (e, "spike")));
+        for (item, layer_default) in items {
+            let kind = match item.property("enemy").unwrap_or(layer_default) {
+                "crab" => EnemyKind::Crab(Patrol::new(item)),
+                "chaser" => EnemyKind::Chaser(Chase::new(item)),
+                "timed_spike" => EnemyKind::TimedSpike(Cycle::new(item)),
+                "turret" => EnemyKind::Turret(Turret::new(item)),
+                _ => EnemyKind::Spike(0.0),
+            };
+            let mut enemy = Enemy::new(item.pos.0, item.pos.1, kind, item);
+            if IS_RANDOM_ENEMY_PHASE {
+                enemy.randomise_phase(lvl);
+            }
+            match enemy.kind {
+                EnemyKind::Crab(_) | EnemyKind::Chaser(_) => moving.push(enemy),
+                _ => stationary.push(enemy),
             }
         }
-        for s in self.spikes.iter_mut() {
-            if s.update(agent_pos) {
-                return true;
+
+        Self {
+            resources: resources.clone(),
+            enemies: moving,
+            spikes: stationary,
+            chase_field: ChaseField::default(),
+            is_swept_collision: IS_SWEPT_COLLISION,
+        }
+    }
+
+    /// `agent_prev` is where the agent was before its move this frame
+    pub fn update(
+        &mut self,
+        agent_prev: &(usize, usize),
+        agent_pos: &(usize, usize),
+        frame: usize,
+    ) -> Option<Collision> {
+        let lvl = &self.resources.lvl_info;
+        let field = &mut self.chase_field;
+        let agent_prev = match self.is_swept_collision {
+            true => Some(agent_prev),
+            false => None,
+        };
+        for e in self.enemies.iter_mut().chain(self.spikes.iter_mut()) {
+            if let Some(hit) = e.update(lvl, field, agent_prev, agent_pos) {
+                return Some(e.collision(hit, frame));
             }
         }
 
-        false
+        None
     }
 
-    pub fn draw(&self, scale_factor: f32, offset_x: f32, offset_y: f32) {
+    /// Current position of every enemy (and projectile), including the ones
+    /// that aren't rendered, along with whether they're deadly right now
+    pub fn positions(&self) -> Vec<((usize, usize), CollisionKind, bool)> {
         self.enemies
             .iter()
-            .for_each(|e| e.draw(scale_factor, offset_x, offset_y));
-        self.spikes
+            .chain(self.spikes.iter())
+            .flat_map(|e| e.positions())
+            .collect()
+    }
+
+    pub fn sprites(&self) -> Vec<EnemySprite> {
+        self.enemies
+            .iter()
+            .chain(self.spikes.iter())
+            .flat_map(|e| e.sprites())
+            .collect()
+    }
+
+    pub fn draw(&self, scale_factor: f32, offset_x: f32, offset_y: f32) {
+        self.sprites()
             .iter()
-            .for_each(|e| e.draw(scale_factor, offset_x, offset_y));
+            .for_each(|s| s.draw(scale_factor, offset_x, offset_y));
     }
 }
 
@@ -72,119 +237,512 @@ impl Enemy {
         }
     }
 
-    fn update(&mut self, agent_pos: &(usize, usize)) -> bool {
-        let (x, y) = agent_pos;
+    /// Starts the enemy somewhere along its patrol or cycle instead of at the
+    /// beginning, so a genome can't just memorise the timing
+    fn randomise_phase(&mut self, lvl: &LevelInfo) {
+        match self.kind {
+            EnemyKind::Crab(ref mut patrol) => {
+                // A full patrol never takes more than two crossings of the room
+                let num_steps = gen_range(0, 2 * lvl.size.0.max(lvl.size.1));
+                for _ in 0..num_steps {
+                    self.pos = patrol.step(lvl, self.pos);
+                }
+            }
+            EnemyKind::TimedSpike(ref mut cycle) => {
+                cycle.offset = gen_range(0, cycle.period);
+            }
+            EnemyKind::Turret(ref mut turret) => {
+                turret.frame = gen_range(0, turret.period);
+            }
+            EnemyKind::Spike(_) | EnemyKind::Chaser(_) => {}
+        }
+    }
 
+    /// Returns the tile the agent got hit on and what hit it, if it did,
+    /// crossing the agent only counts when given where it was before
+    fn update(
+        &mut self,
+        lvl: &LevelInfo,
+        chase_field: &mut ChaseField,
+        agent_prev: Option<&(usize, usize)>,
+        agent_pos: &(usize, usize),
+    ) -> Option<((usize, usize), CollisionKind)> {
+        // Tiles walked by the enemy or its projectiles this frame, each
+        // starting with where it was
+        let mut paths = Vec::new();
         match self.kind {
-            EnemyKind::Crab(v) => {
-                let resources = RESOURCES.get().unwrap();
-                match self.item.value {
-                    88 => {
-                        let mut new_loc = self.pos.clone();
-                        new_loc.1 = match v {
-                            true => new_loc.1 + 1,
-                            false => new_loc.1 - 1,
-                        };
-
-                        let is_wall = resources
-                            .lvl_map
-                            .get_tile(LAYER_WALLS, new_loc.0 as u32, new_loc.1 as u32)
-                            .is_some();
-                        if is_wall {
-                            self.kind = EnemyKind::Crab(!v);
-                            new_loc.1 = match v {
-                                true => new_loc.1 - 2,
-                                false => new_loc.1 + 2,
-                            };
-                        }
-
-                        self.pos = new_loc;
-                    }
-                    _ => {
-                        let mut new_loc = self.pos.clone();
-                        // new_loc.0 += 1;
-                        new_loc.0 = match v {
-                            true => new_loc.0 - 1,
-                            false => new_loc.0 + 1,
-                        };
-
-                        let is_wall = resources
-                            .lvl_map
-                            .get_tile(LAYER_WALLS, new_loc.0 as u32, new_loc.1 as u32)
-                            .is_some();
-                        if is_wall {
-                            self.kind = EnemyKind::Crab(!v);
-                            new_loc.0 = match v {
-                                true => new_loc.0 + 2,
-                                false => new_loc.0 - 2,
-                            };
-                        }
-
-                        self.pos = new_loc;
-                    }
+            EnemyKind::Crab(ref mut patrol) => {
+                let mut path = vec![self.pos];
+                patrol.progress += patrol.speed;
+                while patrol.progress >= 1.0 {
+                    patrol.progress -= 1.0;
+                    self.pos = patrol.step(lvl, self.pos);
+                    path.push(self.pos);
+                }
+                paths.push((path, CollisionKind::Crab));
+            }
+            EnemyKind::Chaser(ref mut chase) => {
+                let mut path = vec![self.pos];
+                chase.progress += chase.speed;
+                while chase.progress >= 1.0 {
+                    chase.progress -= 1.0;
+                    let dist = chase_field.solve(lvl, *agent_pos);
+                    self.pos = Chase::step(dist, self.pos, lvl.size);
+                    path.push(self.pos);
                 }
+                paths.push((path, CollisionKind::Chaser));
             }
             EnemyKind::Spike(v) => {
                 self.kind = EnemyKind::Spike((v + 10.0) % 360.0);
             }
+            EnemyKind::TimedSpike(ref mut cycle) => {
+                cycle.frame += 1;
+            }
+            EnemyKind::Turret(ref mut turret) => {
+                paths.extend(
+                    turret
+                        .update(lvl, self.pos)
+                        .into_iter()
+                        .map(|path| (path, CollisionKind::Projectile)),
+                );
+            }
         }
 
-        if *x == self.pos.0 && *y == self.pos.1 {
-            return true;
+        // Swapping tiles with an enemy or being walked over is a hit too
+        if let Some(agent_prev) = agent_prev {
+            let crossed = paths
+                .iter()
+                .find(|(path, _)| is_crossing(path, agent_prev, agent_pos));
+            if let Some((_, kind)) = crossed {
+                return Some((*agent_pos, *kind));
+            }
         }
 
-        false
+        self.positions()
+            .into_iter()
+            .find(|(pos, _, is_deadly)| *is_deadly && pos == agent_pos)
+            .map(|(pos, kind, _)| (pos, kind))
     }
 
-    pub fn draw(&self, scale_factor: f32, offset_x: f32, offset_y: f32) {
-        let textures = TEXTURES.get().unwrap();
-        let texture = match self.kind {
-            EnemyKind::Crab(_) => textures.crab_texture,
-            EnemyKind::Spike(_) => {
-                match self.item.value {
-                    101 => textures.small_spike_texture,
-                    104 => textures.large_spike_texture,
-                    // Blank spikes (made just for collision, aren't to be rendered)
-                    _ => return,
-                }
+    fn positions(&self) -> Vec<((usize, usize), CollisionKind, bool)> {
+        match &self.kind {
+            EnemyKind::Spike(_) => vec![(self.pos, CollisionKind::Spike, true)],
+            EnemyKind::Crab(_) => vec![(self.pos, CollisionKind::Crab, true)],
+            EnemyKind::Chaser(_) => vec![(self.pos, CollisionKind::Chaser, true)],
+            EnemyKind::TimedSpike(cycle) => {
+                vec![(self.pos, CollisionKind::Spike, cycle.is_extended())]
             }
+            EnemyKind::Turret(turret) => {
+                let mut positions = vec![(self.pos, CollisionKind::Turret, true)];
+                positions.extend(
+                    turret
+                        .projectiles
+                        .iter()
+                        .map(|p| (*p, CollisionKind::Projectile, true)),
+                );
+                positions
+            }
+        }
+    }
+
+    fn collision(&self, (pos, kind): ((usize, usize), CollisionKind), frame: usize) -> Collision {
+        Collision {
+            kind,
+            tile_id: self.item.value,
+            pos,
+            frame,
+        }
+    }
+
+    fn sprites(&self) -> Vec<EnemySprite> {
+        let sprite = |kind, rotation, tint| EnemySprite {
+            pos: self.pos,
+            kind,
+            rotation,
+            tint,
+        };
+        // Spikes set the `sprite` property to `small` or `large`, blank spikes
+        // (made just for collision) aren't rendered
+        let spike_kind = match self.item.property("sprite") {
+            Some("small") => Some(SpriteKind::SmallSpike),
+            Some("large") => Some(SpriteKind::LargeSpike),
+            _ => None,
         };
 
-        let mut render_scale = 1.0;
-        if texture == textures.large_spike_texture {
-            render_scale = 2.0;
+        match &self.kind {
+            EnemyKind::Crab(patrol) => vec![sprite(SpriteKind::Crab, patrol.rotation(), WHITE)],
+            EnemyKind::Chaser(_) => {
+                vec![sprite(
+                    SpriteKind::Crab,
+                    0.0,
+                    Color::from_rgba(255, 90, 90, 255),
+                )]
+            }
+            EnemyKind::Spike(v) => spike_kind
+                .map(|kind| vec![sprite(kind, *v, WHITE)])
+                .unwrap_or_default(),
+            EnemyKind::TimedSpike(cycle) => {
+                let tint = match cycle.is_extended() {
+                    true => WHITE,
+                    false => Color::new(1.0, 1.0, 1.0, 0.3),
+                };
+                let kind = spike_kind.unwrap_or(SpriteKind::SmallSpike);
+                vec![sprite(kind, 0.0, tint)]
+            }
+            EnemyKind::Turret(turret) => {
+                let mut sprites = vec![sprite(SpriteKind::Turret, turret.rotation(), WHITE)];
+                sprites.extend(turret.projectiles.iter().map(|p| EnemySprite {
+                    pos: *p,
+                    kind: SpriteKind::Projectile,
+                    rotation: 0.0,
+                    tint: WHITE,
+                }));
+                sprites
+            }
         }
+    }
+}
 
-        let rotation = match self.kind {
-            EnemyKind::Spike(v) => v,
-            EnemyKind::Crab(v) => match self.item.value {
-                88 => {
-                    if v {
-                        PI
-                    } else {
-                        0.0
-                    }
-                }
-                _ => {
-                    if v {
-                        PI + PI / 2.0
-                    } else {
-                        PI / 2.0
-                    }
-                }
-            },
+impl EnemySprite {
+    pub fn draw(&self, scale_factor: f32, offset_x: f32, offset_y: f32) {
+        let textures = TEXTURES.get().unwrap();
+        let x = self.pos.0 as f32 * scale_factor + offset_x;
+        let y = self.pos.1 as f32 * scale_factor + offset_y;
+        let texture = match self.kind {
+            SpriteKind::Crab => textures.crab_texture,
+            SpriteKind::SmallSpike => textures.small_spike_texture,
+            SpriteKind::LargeSpike => textures.large_spike_texture,
+            // No sprites for these, drawn with shapes
+            SpriteKind::Turret => {
+                let center = vec2(x, y) + Vec2::splat(scale_factor / 2.0);
+                let barrel = center + Vec2::from_angle(self.rotation) * scale_factor / 2.0;
+                draw_rectangle(x, y, scale_factor, scale_factor, DARKGRAY);
+                draw_circle(center.x, center.y, scale_factor * 0.3, GRAY);
+                draw_line(
+                    center.x,
+                    center.y,
+                    barrel.x,
+                    barrel.y,
+                    scale_factor * 0.2,
+                    GRAY,
+                );
+                return;
+            }
+            SpriteKind::Projectile => {
+                let center = vec2(x, y) + Vec2::splat(scale_factor / 2.0);
+                draw_circle(center.x, center.y, scale_factor * 0.2, ORANGE);
+                return;
+            }
         };
 
         draw_texture_ex(
             texture,
-            self.pos.0 as f32 * scale_factor + offset_x,
-            self.pos.1 as f32 * scale_factor + offset_y,
-            WHITE,
+            x,
+            y,
+            self.tint,
             DrawTextureParams {
-                dest_size: Some(Vec2::splat(scale_factor * render_scale)),
-                rotation,
+                dest_size: Some(Vec2::splat(scale_factor * self.kind.render_scale())),
+                rotation: self.rotation,
                 ..Default::default()
             },
         );
     }
 }
+
+impl SpriteKind {
+    /// Size of the sprite in tiles
+    pub fn render_scale(&self) -> f32 {
+        match self {
+            SpriteKind::LargeSpike => 2.0,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Whether an enemy walking `path` (starting with its previous position) ran
+/// into the agent on the way, either by walking over it or by the two
+/// swapping tiles. Checking only the final positions misses both
+pub fn is_crossing(
+    path: &[(usize, usize)],
+    agent_prev: &(usize, usize),
+    agent_pos: &(usize, usize),
+) -> bool {
+    let is_walked_over = path.iter().skip(1).any(|p| p == agent_pos);
+    let is_swapped = path
+        .windows(2)
+        .any(|w| w[0] == *agent_pos && w[1] == *agent_prev);
+
+    is_walked_over || is_swapped
+}
+
+/// Tiles enemies can't walk through
+fn is_blocked(lvl: &LevelInfo, (x, y): (usize, usize)) -> bool {
+    lvl.is_wall((x, y)) || lvl.door == (x, y)
+}
+
+fn property_or<T: std::str::FromStr>(item: &GameItem, name: &str, default: T) -> T {
+    item.property(name)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+impl Patrol {
+    fn new(item: &GameItem) -> Self {
+        let axis = match item.property("axis") {
+            Some("vertical") => Axis::Vertical,
+            _ => Axis::Horizontal,
+        };
+        Self {
+            axis,
+            is_positive: item.property("direction") != Some("negative"),
+            speed: property_or(item, "speed", 1.0),
+            progress: 0.0,
+            bounds: (
+                property_or(item, "min", 0),
+                property_or(item, "max", usize::MAX),
+            ),
+            // Levels with broken paths fail to load, see `LevelInfo::new`
+            path: item
+                .property("path")
+                .and_then(|path| parse_path(path).ok())
+                .unwrap_or_default(),
+            path_idx: 0,
+        }
+    }
+
+    /// Moves one tile, turning around at walls and patrol bounds, or at the
+    /// ends of the path
+    fn step(&mut self, lvl: &LevelInfo, pos: (usize, usize)) -> (usize, usize) {
+        if !self.path.is_empty() {
+            return self.step_path();
+        }
+
+        match self.next(lvl, pos) {
+            Some(next) => next,
+            None => {
+                self.is_positive = !self.is_positive;
+                self.next(lvl, pos).unwrap_or(pos)
+            }
+        }
+    }
+
+    fn next(&self, lvl: &LevelInfo, (x, y): (usize, usize)) -> Option<(usize, usize)> {
+        let delta = if self.is_positive { 1 } else { -1 };
+        let next = match self.axis {
+            Axis::Horizontal => (x.checked_add_signed(delta)?, y),
+            Axis::Vertical => (x, y.checked_add_signed(delta)?),
+        };
+        let along = match self.axis {
+            Axis::Horizontal => next.0,
+            Axis::Vertical => next.1,
+        };
+        if along < self.bounds.0 || along > self.bounds.1 {
+            return None;
+        }
+
+        match lvl.is_wall(next) {
+            true => None,
+            false => Some(next),
+        }
+    }
+
+    fn step_path(&mut self) -> (usize, usize) {
+        let last = self.path.len() - 1;
+        if (self.is_positive && self.path_idx == last) || (!self.is_positive && self.path_idx == 0)
+        {
+            self.is_positive = !self.is_positive;
+        }
+        self.path_idx = match self.is_positive {
+            true => (self.path_idx + 1).min(last),
+            false => self.path_idx.saturating_sub(1),
+        };
+
+        self.path[self.path_idx]
+    }
+
+    /// The crab sprite faces up, rotate it to face the way it's walking
+    fn rotation(&self) -> f32 {
+        if !self.path.is_empty() {
+            // Towards the next tile of the path
+            let (x, y) = self.path[self.path_idx];
+            let next = match self.is_positive {
+                true => self.path.get(self.path_idx + 1),
+                false => self.path_idx.checked_sub(1).and_then(|i| self.path.get(i)),
+            };
+            return match next {
+                Some(&(nx, _)) if nx > x => PI / 2.0,
+                Some(&(nx, _)) if nx < x => PI + PI / 2.0,
+                Some(&(_, ny)) if ny > y => PI,
+                _ => 0.0,
+            };
+        }
+
+        match (self.axis, self.is_positive) {
+            (Axis::Vertical, true) => PI,
+            (Axis::Vertical, false) => 0.0,
+            (Axis::Horizontal, true) => PI / 2.0,
+            (Axis::Horizontal, false) => PI + PI / 2.0,
+        }
+    }
+}
+
+/// Tiles between the `x,y;x,y` waypoints of a path property, one step at a
+/// time so a crab never skips a tile. Segments have to be horizontal or
+/// vertical, a diagonal one would be walked as an L instead
+pub(crate) fn parse_path(path: &str) -> Result<Vec<(usize, usize)>, String> {
+    let waypoints = path
+        .split(';')
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| {
+            let (x, y) = p.split_once(',')?;
+            Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+        })
+        .collect::<Option<Vec<(usize, usize)>>>()
+        .ok_or_else(|| format!("Path {} isn't a list of x,y tiles", path))?;
+
+    let mut tiles = waypoints.first().into_iter().copied().collect::<Vec<_>>();
+    for &(tx, ty) in waypoints.iter().skip(1) {
+        let mut pos = *tiles.last().unwrap();
+        if pos.0 != tx && pos.1 != ty {
+            return Err(format!(
+                "Path segment {:?} to {:?} isn't horizontal or vertical",
+                pos,
+                (tx, ty)
+            ));
+        }
+        while pos != (tx, ty) {
+            pos = match pos.0 != tx {
+                true if pos.0 < tx => (pos.0 + 1, pos.1),
+                true => (pos.0 - 1, pos.1),
+                false if pos.1 < ty => (pos.0, pos.1 + 1),
+                false => (pos.0, pos.1 - 1),
+            };
+            tiles.push(pos);
+        }
+    }
+
+    Ok(tiles)
+}
+
+impl Chase {
+    fn new(item: &GameItem) -> Self {
+        Self {
+            speed: property_or(item, "speed", 0.5),
+            progress: 0.0,
+        }
+    }
+
+    /// One tile closer to the agent, the chaser stays put when the agent
+    /// can't be reached
+    fn step(dist: &[usize], pos: (usize, usize), size: (usize, usize)) -> (usize, usize) {
+        neighbours(pos, size)
+            .into_iter()
+            .filter(|n| dist[n.1 * size.0 + n.0] < dist[pos.1 * size.0 + pos.0])
+            .min_by_key(|n| dist[n.1 * size.0 + n.0])
+            .unwrap_or(pos)
+    }
+}
+
+impl ChaseField {
+    /// Flow field spreading out from the agent, walls and the door block it
+    fn solve(&mut self, lvl: &LevelInfo, agent_pos: (usize, usize)) -> &[usize] {
+        if self.agent_pos == Some(agent_pos) {
+            return &self.dist;
+        }
+
+        let size = lvl.size;
+        self.dist = vec![usize::MAX; size.0 * size.1];
+        let mut q = VecDeque::from([agent_pos]);
+        self.dist[agent_pos.1 * size.0 + agent_pos.0] = 0;
+        while let Some((x, y)) = q.pop_front() {
+            let d = self.dist[y * size.0 + x];
+            for n in neighbours((x, y), size) {
+                if self.dist[n.1 * size.0 + n.0] == usize::MAX && !is_blocked(lvl, n) {
+                    self.dist[n.1 * size.0 + n.0] = d + 1;
+                    q.push_back(n);
+                }
+            }
+        }
+        self.agent_pos = Some(agent_pos);
+
+        &self.dist
+    }
+}
+
+fn neighbours((x, y): (usize, usize), size: (usize, usize)) -> Vec<(usize, usize)> {
+    [(1, 0), (-1, 0), (0, 1), (0, -1)]
+        .iter()
+        .filter_map(|(dx, dy)| Some((x.checked_add_signed(*dx)?, y.checked_add_signed(*dy)?)))
+        .filter(|(x, y)| *x < size.0 && *y < size.1)
+        .collect()
+}
+
+impl Cycle {
+    fn new(item: &GameItem) -> Self {
+        Self {
+            period: property_or(item, "period", 20usize).max(1),
+            active: property_or(item, "active", 10),
+            offset: property_or(item, "offset", 0),
+            frame: 0,
+        }
+    }
+
+    fn is_extended(&self) -> bool {
+        (self.frame + self.offset) % self.period < self.active
+    }
+}
+
+impl Turret {
+    fn new(item: &GameItem) -> Self {
+        let dir = match item.property("direction") {
+            Some("up") => (0, -1),
+            Some("down") => (0, 1),
+            Some("left") => (-1, 0),
+            _ => (1, 0),
+        };
+
+        Self {
+            dir,
+            period: property_or(item, "period", 10usize).max(1),
+            frame: 0,
+            projectiles: Vec::new(),
+        }
+    }
+
+    fn next(
+        lvl: &LevelInfo,
+        (x, y): (usize, usize),
+        (dx, dy): (isize, isize),
+    ) -> Option<(usize, usize)> {
+        let next = (x.checked_add_signed(dx)?, y.checked_add_signed(dy)?);
+        match is_blocked(lvl, next) {
+            true => None,
+            false => Some(next),
+        }
+    }
+
+    /// Moves the projectiles and fires a new one when due. Returns the tiles
+    /// each projectile went through this frame, new ones start at the turret
+    fn update(&mut self, lvl: &LevelInfo, pos: (usize, usize)) -> Vec<Vec<(usize, usize)>> {
+        let dir = self.dir;
+        let mut paths: Vec<_> = self
+            .projectiles
+            .iter()
+            .filter_map(|p| Some(vec![*p, Turret::next(lvl, *p, dir)?]))
+            .collect();
+
+        if self.frame.is_multiple_of(self.period) {
+            if let Some(p) = Turret::next(lvl, pos, dir) {
+                paths.push(vec![pos, p]);
+            }
+        }
+        self.frame += 1;
+        self.projectiles = paths.iter().map(|path| path[1]).collect();
+
+        paths
+    }
+
+    fn rotation(&self) -> f32 {
+        (self.dir.1 as f32).atan2(self.dir.0 as f32)
+    }
+}
@@ -0,0 +1,69 @@
+use std::fs;
+
+use crate::game::Game;
+use crate::resources::init_resources_headless;
+use crate::*;
+
+/// Outcome of one episode of a genome with a fixed spawn and key
+pub struct EvalResult {
+    pub spawn: (usize, usize),
+    pub key: (usize, usize),
+    pub is_complete: bool,
+    pub is_dead: bool,
+}
+
+/// Plays the moves from every spawn candidate with every key candidate.
+/// A genome that only memorised its training spawn fails most of these
+pub fn evaluate(moves: &[u8]) -> Vec<EvalResult> {
+    let lvl = &RESOURCES.get().unwrap().lvl_info;
+    let mut results = Vec::new();
+    for spawn in lvl.spawn_candidates.iter() {
+        for key in lvl.key_candidates.iter() {
+            let mut game = Game::with_layout(moves, *spawn, *key);
+            for frame_count in 0..NUM_FRAMES {
+                game.update(frame_count);
+            }
+
+            results.push(EvalResult {
+                spawn: *spawn,
+                key: *key,
+                is_complete: game.is_complete,
+                is_dead: game.is_dead,
+            });
+        }
+    }
+
+    results
+}
+
+/// Percentage of episodes that reached the door
+pub fn success_rate(results: &[EvalResult]) -> f32 {
+    if results.is_empty() {
+        return 0.0;
+    }
+
+    let num_complete = results.iter().filter(|r| r.is_complete).count();
+    num_complete as f32 / results.len() as f32 * 100.0
+}
+
+/// Evaluates a genome file (as saved by the recorder) and prints every
+/// episode along with the overall success rate
+pub fn run(genome_path: &str) {
+    init_resources_headless();
+
+    let genome = fs::read_to_string(genome_path).expect("Failed to read the genome file");
+    let results = evaluate(&Game::parse_moves(&genome));
+    for r in results.iter() {
+        let outcome = match (r.is_complete, r.is_dead) {
+            (true, _) => "complete",
+            (_, true) => "dead",
+            _ => "timeout",
+        };
+        println!("Spawn {:?}  Key {:?}  {}", r.spawn, r.key, outcome);
+    }
+    println!(
+        "Success rate: {:.1}% over {} episodes",
+        success_rate(&results),
+        results.len()
+    );
+}
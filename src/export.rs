@@ -0,0 +1,325 @@
+use macroquad_tiled::{Map, Property};
+
+use crate::*;
+
+/// Tile layer of a level, gids as Tiled stores them (`0` is an empty tile)
+#[derive(Clone, Debug, PartialEq)]
+pub struct TileLayer {
+    pub name: String,
+    // Row major, `size.0 * size.1` tiles
+    pub data: Vec<u32>,
+    pub is_visible: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Tileset {
+    pub name: String,
+    pub image: String,
+    pub image_size: (i32, i32),
+    pub first_gid: u32,
+    pub columns: i32,
+    pub tile_count: u32,
+    pub tile_size: (i32, i32),
+    pub margin: i32,
+    pub spacing: i32,
+    /// Custom properties by tile id, see the enemy properties in the README
+    pub tile_properties: Vec<(usize, Vec<Property>)>,
+}
+
+/// Object layer of a level, the objects as Tiled stores them. Polylines
+/// are kept as the `path` property they were turned into on load
+#[derive(Clone, Debug)]
+pub struct ObjectLayer {
+    pub name: String,
+    pub objects: Vec<Object>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Object {
+    pub name: String,
+    pub ty: String,
+    pub gid: Option<u32>,
+    // In pixels, tile objects are anchored at their bottom left corner
+    pub pos: (f32, f32),
+    pub size: (f32, f32),
+    pub properties: Vec<Property>,
+}
+
+/// A level laid out like a Tiled map, ready to be written back to JSON
+#[derive(Clone, Debug)]
+pub struct TiledLevel {
+    pub size: (usize, usize),
+    pub layers: Vec<TileLayer>,
+    pub object_layers: Vec<ObjectLayer>,
+    pub tilesets: Vec<Tileset>,
+}
+
+impl Default for Tileset {
+    /// The tileset of the shipped levels, with the crab and spike properties
+    fn default() -> Self {
+        let property = |name: &str, value: &str| Property {
+            name: name.to_string(),
+            ty: "string".to_string(),
+            value: value.to_string(),
+        };
+
+        Self {
+            name: "tileset".to_string(),
+            image: TILE_SET_NAME.to_string(),
+            image_size: (100, 100),
+            first_gid: 1,
+            columns: 12,
+            tile_count: 144,
+            tile_size: (8, 8),
+            margin: 0,
+            spacing: 0,
+            tile_properties: vec![
+                (
+                    77,
+                    vec![
+                        property("axis", "horizontal"),
+                        property("direction", "positive"),
+                    ],
+                ),
+                (
+                    88,
+                    vec![
+                        property("axis", "vertical"),
+                        property("direction", "negative"),
+                    ],
+                ),
+                (101, vec![property("sprite", "small")]),
+                (104, vec![property("sprite", "large")]),
+            ],
+        }
+    }
+}
+
+impl TiledLevel {
+    /// Layers and tilesets of a loaded map, in the order of the file
+    pub fn from_map(map: &Map) -> Self {
+        let raw = &map.raw_tiled_map;
+        let layers = raw
+            .layers
+            .iter()
+            .filter(|l| l.ty == "tilelayer")
+            .map(|l| TileLayer {
+                name: l.name.clone(),
+                data: l.data.clone(),
+                is_visible: l.visible,
+            })
+            .collect();
+        let object_layers = raw
+            .layers
+            .iter()
+            .filter(|l| l.ty == "objectgroup")
+            .map(|l| ObjectLayer {
+                name: l.name.clone(),
+                objects: l
+                    .objects
+                    .iter()
+                    .map(|o| Object {
+                        name: o.name.clone(),
+                        ty: o.ty.clone(),
+                        gid: o.gid,
+                        pos: (o.x, o.y),
+                        size: (o.width, o.height),
+                        properties: o
+                            .properties
+                            .iter()
+                            .map(|p| Property {
+                                name: p.name.clone(),
+                                ty: p.ty.clone(),
+                                value: p.value.clone(),
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            })
+            .collect();
+        let tilesets = raw
+            .tilesets
+            .iter()
+            .map(|t| Tileset {
+                name: t.name.clone(),
+                image: t.image.clone(),
+                image_size: (t.imagewidth, t.imageheight),
+                first_gid: t.firstgid,
+                columns: t.columns,
+                tile_count: t.tilecount,
+                tile_size: (t.tilewidth, t.tileheight),
+                margin: t.margin,
+                spacing: t.spacing,
+                tile_properties: t
+                    .tiles
+                    .iter()
+                    .filter(|tile| !tile.properties.is_empty())
+                    .map(|tile| {
+                        let properties = tile
+                            .properties
+                            .iter()
+                            .map(|p| Property {
+                                name: p.name.clone(),
+                                ty: p.ty.clone(),
+                                value: p.value.clone(),
+                            })
+                            .collect();
+                        (tile.id, properties)
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Self {
+            size: (raw.width as usize, raw.height as usize),
+            layers,
+            object_layers,
+            tilesets,
+        }
+    }
+
+    pub fn layer(&self, name: &str) -> Option<&TileLayer> {
+        self.layers.iter().find(|l| l.name == name)
+    }
+
+    /// Serialises the level in the format Tiled saves maps in
+    pub fn to_json(&self) -> String {
+        let (w, h) = self.size;
+        let mut layers: Vec<String> = self
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| {
+                let data: Vec<String> = layer.data.iter().map(|g| g.to_string()).collect();
+                format!(
+                    r#"{{"data":[{}],"height":{},"id":{},"name":{},"opacity":1,"type":"tilelayer","visible":{},"width":{},"x":0,"y":0}}"#,
+                    data.join(","),
+                    h,
+                    i + 1,
+                    json_string(&layer.name),
+                    layer.is_visible,
+                    w
+                )
+            })
+            .collect();
+        // Objects are numbered across all the object layers
+        let mut num_objects = 0;
+        for layer in self.object_layers.iter() {
+            let objects: Vec<String> = layer
+                .objects
+                .iter()
+                .map(|o| {
+                    num_objects += 1;
+                    o.to_json(num_objects)
+                })
+                .collect();
+            layers.push(format!(
+                r#"{{"draworder":"topdown","id":{},"name":{},"objects":[{}],"opacity":1,"type":"objectgroup","visible":true,"x":0,"y":0}}"#,
+                layers.len() + 1,
+                json_string(&layer.name),
+                objects.join(",")
+            ));
+        }
+        let tilesets: Vec<String> = self.tilesets.iter().map(|t| t.to_json()).collect();
+        let tile_size = self.tilesets.first().map(|t| t.tile_size).unwrap_or((8, 8));
+
+        format!(
+            r#"{{"compressionlevel":-1,"height":{},"infinite":false,"layers":[{}],"nextlayerid":{},"nextobjectid":{},"orientation":"orthogonal","renderorder":"right-down","tiledversion":"1.10.0","tileheight":{},"tilesets":[{}],"tilewidth":{},"type":"map","version":"1.10","width":{}}}"#,
+            h,
+            layers.join(","),
+            layers.len() + 1,
+            num_objects + 1,
+            tile_size.1,
+            tilesets.join(","),
+            tile_size.0,
+            w
+        )
+    }
+}
+
+impl Object {
+    fn to_json(&self, id: usize) -> String {
+        let properties: Vec<String> = self
+            .properties
+            .iter()
+            .map(|p| property_json(&p.name, &p.ty, &p.value))
+            .collect();
+        let gid = match self.gid {
+            Some(gid) => format!(r#""gid":{},"#, gid),
+            None => String::new(),
+        };
+
+        format!(
+            r#"{{{}"height":{},"id":{},"name":{},"properties":[{}],"rotation":0,"type":{},"visible":true,"width":{},"x":{},"y":{}}}"#,
+            gid,
+            self.size.1,
+            id,
+            json_string(&self.name),
+            properties.join(","),
+            json_string(&self.ty),
+            self.size.0,
+            self.pos.0,
+            self.pos.1
+        )
+    }
+}
+
+impl Tileset {
+    fn to_json(&self) -> String {
+        let tiles: Vec<String> = self
+            .tile_properties
+            .iter()
+            .map(|(id, properties)| {
+                let properties: Vec<String> = properties
+                    .iter()
+                    .map(|p| property_json(&p.name, &p.ty, &p.value))
+                    .collect();
+                format!(r#"{{"id":{},"properties":[{}]}}"#, id, properties.join(","))
+            })
+            .collect();
+
+        format!(
+            r#"{{"columns":{},"firstgid":{},"image":{},"imageheight":{},"imagewidth":{},"margin":{},"name":{},"spacing":{},"tilecount":{},"tileheight":{},"tiles":[{}],"tilewidth":{}}}"#,
+            self.columns,
+            self.first_gid,
+            json_string(&self.image),
+            self.image_size.1,
+            self.image_size.0,
+            self.margin,
+            json_string(&self.name),
+            self.spacing,
+            self.tile_count,
+            self.tile_size.1,
+            tiles.join(","),
+            self.tile_size.0
+        )
+    }
+}
+
+fn property_json(name: &str, ty: &str, value: &str) -> String {
+    format!(
+        r#"{{"name":{},"type":{},"value":{}}}"#,
+        json_string(name),
+        json_string(ty),
+        json_string(value)
+    )
+}
+
+/// Quoted JSON string, control characters are escaped as JSON requires
+pub(crate) fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+
+    escaped
+}
@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+use macroquad::prelude::*;
+
 use crate::*;
 
 pub struct FF {
@@ -8,16 +10,25 @@ pub struct FF {
     weight: usize,
     start_pos: (usize, usize),
     grid_bounds: (usize, usize),
+    // Row major, `grid_bounds.0 * grid_bounds.1` tiles
+    walls: Vec<bool>,
 }
 
 impl FF {
     pub fn new(start_pos: &(usize, usize), size: &(usize, usize)) -> Self {
+        let walls = RESOURCES.get().unwrap().lvl_info.walls.clone();
+        FF::with_walls(start_pos, size, walls)
+    }
+
+    /// Field over a wall grid that isn't the loaded level, like a generated room
+    pub fn with_walls(start_pos: &(usize, usize), size: &(usize, usize), walls: Vec<bool>) -> Self {
         FF {
             q: Vec::new(),
             mat: HashMap::new(),
             weight: 0,
-            start_pos: start_pos.clone(),
-            grid_bounds: size.clone(),
+            start_pos: *start_pos,
+            grid_bounds: *size,
+            walls,
         }
     }
 
@@ -26,16 +37,11 @@ impl FF {
             return;
         }
 
-        if x > self.grid_bounds.0 || y > self.grid_bounds.1 {
+        if x >= self.grid_bounds.0 || y >= self.grid_bounds.1 {
             return;
         }
 
-        let tile = RESOURCES
-            .get()
-            .unwrap()
-            .lvl_map
-            .get_tile(LAYER_WALLS, x as u32, y as u32);
-        if tile.is_some() {
+        if self.walls[y * self.grid_bounds.0 + x] {
             return;
         }
 
@@ -58,9 +64,9 @@ impl FF {
             self.mat.insert((x, y), self.weight);
 
             self.update_q((x + 1, y));
-            self.update_q((x - 1, y));
+            self.update_q((x.wrapping_sub(1), y));
             self.update_q((x, y + 1));
-            self.update_q((x, y - 1));
+            self.update_q((x, y.wrapping_sub(1)));
         }
     }
 
@@ -71,4 +77,67 @@ impl FF {
 
         self.mat.clone()
     }
+
+    /// Debug view of a solved field, a gradient from the target outwards
+    /// with arrows pointing along the descending direction
+    pub fn draw(
+        field: &HashMap<(usize, usize), usize>,
+        is_show_values: bool,
+        scale_factor: f32,
+        offset_x: f32,
+        offset_y: f32,
+    ) {
+        let max_weight = field.values().copied().max().unwrap_or(1) as f32;
+        let near = Color::from_rgba(116, 242, 145, 255);
+        let far = Color::from_rgba(120, 60, 200, 255);
+
+        for (&(x, y), &weight) in field.iter() {
+            let t = weight as f32 / max_weight;
+            let tile_x = x as f32 * scale_factor + offset_x;
+            let tile_y = y as f32 * scale_factor + offset_y;
+            draw_rectangle(
+                tile_x,
+                tile_y,
+                scale_factor,
+                scale_factor,
+                Color::new(
+                    near.r + (far.r - near.r) * t,
+                    near.g + (far.g - near.g) * t,
+                    near.b + (far.b - near.b) * t,
+                    0.45,
+                ),
+            );
+
+            // Neighbour closest to the target
+            let next = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                .iter()
+                .filter_map(|(dx, dy)| {
+                    let nx = x.checked_add_signed(*dx)?;
+                    let ny = y.checked_add_signed(*dy)?;
+                    field.get(&(nx, ny)).map(|w| (*w, (*dx, *dy)))
+                })
+                .min();
+            if let Some((next_weight, (dx, dy))) = next {
+                if next_weight < weight {
+                    let center = vec2(tile_x, tile_y) + Vec2::splat(scale_factor / 2.0);
+                    let dir = vec2(dx as f32, dy as f32);
+                    let tip = center + dir * scale_factor * 0.35;
+                    let side = vec2(-dir.y, dir.x) * scale_factor * 0.12;
+                    let base = tip - dir * scale_factor * 0.2;
+                    draw_line(center.x, center.y, base.x, base.y, 3.0, WHITE);
+                    draw_triangle(tip, base + side, base - side, WHITE);
+                }
+            }
+
+            if is_show_values {
+                draw_text(
+                    &weight.to_string(),
+                    tile_x + 4.0,
+                    tile_y + scale_factor * 0.3,
+                    scale_factor * 0.35,
+                    WHITE,
+                );
+            }
+        }
+    }
 }
@@ -1,64 +1,194 @@
+use std::sync::Arc;
+
 use macroquad::prelude::*;
 use macroquad::rand::gen_range;
 
-use crate::agent::{Agent, AgentCommand};
-use crate::enemy::EnemyManager;
-use crate::level::LevelInfo;
+use crate::agent::{ActionSpace, Agent, AgentCommand};
+use crate::enemy::{Collision, CollisionKind, EnemyManager, EnemySprite};
+use crate::level::{is_tile_layer, LevelInfo};
+use crate::resources::Resources;
 use crate::*;
 
+/// Parts of a game's fitness, `total` is what selection uses
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FitnessBreakdown {
+    // Flow field distances from the agent to the key and the door
+    pub key_distance: usize,
+    pub door_distance: usize,
+    pub key_score: f32,
+    pub door_score: f32,
+    // Flat bonus for holding the key, or for finishing the room
+    pub bonus: f32,
+    // Fitness lost to dying
+    pub death_penalty: f32,
+    pub total: f32,
+}
+
 #[derive(Clone)]
 pub struct Game {
+    // Index of the level in `RESOURCES`
+    pub level: usize,
+    pub action_space: ActionSpace,
+    // Percentage of moves replaced by a random one, `ACTION_NOISE` by default
+    pub action_noise: f32,
     pub lvl: LevelInfo,
     pub fitness: f32,
     pub is_key_collected: bool,
     pub is_complete: bool,
     pub is_dead: bool,
+    pub death: Option<Collision>,
 
+    // Drawn from, so the draws don't look the level up in `RESOURCES`
+    resources: Arc<Resources>,
     agent: Agent,
     moves: Vec<u8>,
+    // Agent positions visited so far this episode
+    trail: Vec<(usize, usize)>,
     enemy_manager: EnemyManager,
+    // Enemies as they were at the start of the episode, see `restart`
+    start_enemies: EnemyManager,
 
     // Steps it took to complete key and door step
     num_key_steps: u32,
     num_door_steps: u32,
 }
 
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Game {
     pub fn new() -> Self {
-        let resources = RESOURCES.get().unwrap();
-        let lvl = resources.lvl_info.clone();
+        Game::on_level(0)
+    }
+
+    pub fn on_level(level: usize) -> Self {
+        Game::with_action_space(level, ACTION_SPACE)
+    }
+
+    /// Random genome drawn from the moves of the action space
+    pub fn with_action_space(level: usize, action_space: ActionSpace) -> Self {
+        let resources = RESOURCES.level(level).unwrap();
+        let mut lvl = resources.lvl_info.clone();
+        if IS_RANDOM_SPAWN {
+            lvl.agent = lvl.spawn_candidates[gen_range(0, lvl.spawn_candidates.len())];
+        }
+        if IS_RANDOM_KEY {
+            lvl.key = lvl.key_candidates[gen_range(0, lvl.key_candidates.len())];
+        }
+
+        let mut agent = Agent::on_level(level);
+        agent.pos = lvl.agent;
+        let enemy_manager = EnemyManager::on_level(level, lvl.enemies.clone(), lvl.spikes.clone());
         Self {
-            enemy_manager: EnemyManager::new(lvl.enemies.clone(), lvl.spikes.clone()),
-            agent: Agent::new(),
-            moves: (0..NUM_FRAMES).map(|_| gen_range(0, 4)).collect(),
+            level,
+            action_space,
+            action_noise: ACTION_NOISE,
+            start_enemies: enemy_manager.clone(),
+            enemy_manager,
+            agent,
+            trail: vec![lvl.agent],
+            moves: (0..NUM_FRAMES).map(|_| action_space.sample()).collect(),
 
             // At last to avoid borrow error
             lvl,
+            resources,
 
             is_key_collected: false,
             is_complete: false,
             is_dead: false,
+            death: None,
             num_key_steps: 0,
             num_door_steps: 0,
-            fitness: 0.0
+            fitness: 0.0,
         }
     }
 
-    pub fn with_moves(moves: &Vec<u8>) -> Self {
-        let mut g = Game::new();
-        g.moves = moves.clone();
+    pub fn with_moves(moves: &[u8]) -> Self {
+        Game::on_level_with_moves(0, moves)
+    }
+
+    pub fn on_level_with_moves(level: usize, moves: &[u8]) -> Self {
+        let mut g = Game::on_level(level);
+        g.moves = moves.to_vec();
+
+        g
+    }
+
+    /// Episode with a fixed agent spawn and key, used to evaluate a genome
+    pub fn with_layout(moves: &[u8], agent: (usize, usize), key: (usize, usize)) -> Self {
+        let mut g = Game::with_moves(moves);
+        g.lvl.agent = agent;
+        g.lvl.key = key;
+        g.agent.pos = agent;
+        g.trail = vec![agent];
 
         g
     }
 
     pub fn clone_with_moves(parent: &Game) -> Self {
-        Game::with_moves(&parent.moves)
+        let mut g = Game::on_level_with_moves(parent.level, &parent.moves);
+        g.action_space = parent.action_space;
+        g.action_noise = parent.action_noise;
+
+        g
+    }
+
+    /// The same episode from the start, with the spawn, key and enemy
+    /// phases this game drew instead of new ones
+    pub fn restart(&self) -> Self {
+        let mut agent = Agent::on_level(self.level);
+        agent.pos = self.lvl.agent;
+        Self {
+            agent,
+            enemy_manager: self.start_enemies.clone(),
+            trail: vec![self.lvl.agent],
+            is_key_collected: false,
+            is_complete: false,
+            is_dead: false,
+            death: None,
+            num_key_steps: 0,
+            num_door_steps: 0,
+            fitness: 0.0,
+            ..self.clone()
+        }
     }
 
     pub fn get_current_agent_pos(&self) -> &(usize, usize) {
         &self.agent.pos
     }
 
+    pub fn moves(&self) -> &[u8] {
+        &self.moves
+    }
+
+    /// Moves as a string of digits, one per frame
+    pub fn moves_string(&self) -> String {
+        Game::moves_to_string(&self.moves)
+    }
+
+    /// Inverse of `parse_moves`
+    pub fn moves_to_string(moves: &[u8]) -> String {
+        moves.iter().map(|m| m.to_string()).collect()
+    }
+
+    pub fn parse_moves(s: &str) -> Vec<u8> {
+        s.chars()
+            .filter_map(|c| c.to_digit(10))
+            .map(|d| d as u8)
+            .collect()
+    }
+
+    pub fn enemy_positions(&self) -> Vec<((usize, usize), CollisionKind, bool)> {
+        self.enemy_manager.positions()
+    }
+
+    pub fn enemy_sprites(&self) -> Vec<EnemySprite> {
+        self.enemy_manager.sprites()
+    }
+
     pub fn update(&mut self, frame_count: usize) {
         if self.is_complete {
             return;
@@ -74,41 +204,94 @@ impl Game {
             self.num_key_steps += 1;
         }
 
-        let command = AgentCommand::from_int(*self.moves.get(frame_count).unwrap_or(&0));
+        let mut command = AgentCommand::from_int(*self.moves.get(frame_count).unwrap_or(&0));
+        if gen_range(0.0, 100.0) < self.action_noise {
+            command = AgentCommand::from_int(self.action_space.sample());
+        }
+        let agent_prev = self.agent.pos;
         self.agent.update(command, self.is_key_collected);
+        if self.trail.last() != Some(&self.agent.pos) {
+            self.trail.push(self.agent.pos);
+        }
 
-        self.is_dead = self.enemy_manager.update(&self.agent.pos);
+        self.death = self
+            .enemy_manager
+            .update(&agent_prev, &self.agent.pos, frame_count);
+        self.is_dead = self.death.is_some();
         self.handle_key_collision();
         self.is_complete = self.check_player_at_door();
     }
 
+    /// Plays the moves of this game from the start in a fresh episode, which
+    /// only differs from this one in a stochastic environment
+    pub fn rollout(&self) -> Game {
+        let mut game = Game::clone_with_moves(self);
+        (0..NUM_FRAMES).for_each(|frame_count| game.update(frame_count));
+
+        game
+    }
+
     pub fn fitness(&mut self, ff_key: &usize, ff_door: &usize) -> f32 {
+        self.fitness = self.fitness_breakdown(ff_key, ff_door).total;
+
+        self.fitness
+    }
+
+    pub fn fitness_breakdown(&self, ff_key: &usize, ff_door: &usize) -> FitnessBreakdown {
+        let mut breakdown = self.progress_fitness(ff_key, ff_door);
+        breakdown.key_distance = *ff_key;
+        breakdown.door_distance = *ff_door;
+        breakdown.total = breakdown.key_score + breakdown.door_score + breakdown.bonus;
+        if self.is_dead {
+            let total = breakdown.total * (1.0 - DEATH_PENALTY / 100.0);
+            breakdown.death_penalty = breakdown.total - total;
+            breakdown.total = total;
+        }
+
+        breakdown
+    }
+
+    fn progress_fitness(&self, ff_key: &usize, ff_door: &usize) -> FitnessBreakdown {
         if self.is_complete {
             let key_val = NUM_FRAMES as f32 - self.num_key_steps as f32 + 1.0;
             let door_val = NUM_FRAMES as f32 - self.num_door_steps as f32 + 1.0;
-            self.fitness = key_val * 20.0 + door_val * 20.0 + FF_WEIGHT_THRESHOLD * 2.0;
-            return self.fitness;
+            return FitnessBreakdown {
+                key_score: key_val * 20.0,
+                door_score: door_val * 20.0,
+                bonus: FF_WEIGHT_THRESHOLD * 2.0,
+                ..Default::default()
+            };
         }
 
         if !self.is_key_collected {
-            self.fitness = 1.0 / *ff_key as f32 * 1000.0;
-            return self.fitness;
+            return FitnessBreakdown {
+                key_score: 1.0 / *ff_key as f32 * 1000.0,
+                ..Default::default()
+            };
         }
 
         let f_key = 10.0 + (NUM_FRAMES as f32 / self.num_key_steps as f32);
         let f_door = (1.0 / *ff_door as f32) * 1000.0;
-        self.fitness = f_door + f_key;
 
-        if self.is_key_collected {
-            self.fitness += 1000.0;
+        FitnessBreakdown {
+            key_score: f_key,
+            door_score: f_door,
+            bonus: 1000.0,
+            ..Default::default()
         }
+    }
 
-        self.fitness
+    /// Frames it took to reach the key and the door, so far this episode
+    pub fn num_steps(&self) -> (u32, u32) {
+        (self.num_key_steps, self.num_door_steps)
     }
 
-    pub fn update_manual(&mut self, command: AgentCommand) {
-        self.moves[0] = command.to_int();
-        self.update(0);
+    /// Plays a move picked by hand at this frame, the genome is cut there
+    /// so it holds exactly the moves played so far
+    pub fn play_move(&mut self, frame_count: usize, command: AgentCommand) {
+        self.moves.truncate(frame_count);
+        self.moves.push(command.to_int());
+        self.update(frame_count);
     }
 
     pub fn crossover(first: &Self, second: &Self) -> Self {
@@ -116,15 +299,30 @@ impl Game {
         let mut new_moves = Vec::from_iter(first.moves[0..split_point].iter().cloned());
         new_moves.extend_from_slice(&second.moves[split_point..]);
 
-        for m in new_moves.iter_mut() {
-            if gen_range(0.0, 1.0) > (MUTATION_PROBABILITY as f32) * 0.001 {
+        Game::with_mutated_moves(first, new_moves)
+    }
+
+    /// Child of a single parent, its moves with the mutations of `crossover`
+    pub fn mutate(parent: &Self) -> Self {
+        Game::with_mutated_moves(parent, parent.moves.clone())
+    }
+
+    /// New game on the parent's level, with each move replaced by a random
+    /// one at the `MUTATION_PROBABILITY` rate
+    fn with_mutated_moves(parent: &Self, mut moves: Vec<u8>) -> Self {
+        for m in moves.iter_mut() {
+            if gen_range(0.0, 1.0) > MUTATION_PROBABILITY * 0.001 {
                 continue;
             }
 
-            *m = gen_range(0, 4);
+            *m = parent.action_space.sample();
         }
 
-        Game::with_moves(&new_moves)
+        let mut g = Game::on_level_with_moves(parent.level, &moves);
+        g.action_space = parent.action_space;
+        g.action_noise = parent.action_noise;
+
+        g
     }
 
     fn check_player_at_door(&self) -> bool {
@@ -147,11 +345,20 @@ impl Game {
     }
 
     pub fn draw(&self, offset_x: f32, offset_y: f32) {
-        let resources = RESOURCES.get().unwrap();
-        let textures = TEXTURES.get().unwrap();
-        let scale_factor = UNIT_FRAME_SIZE * FRAME_SCALE;
-        let w = self.lvl.size.0 as f32 * scale_factor;
-        let h = self.lvl.size.1 as f32 * scale_factor;
+        self.draw_background(offset_x, offset_y);
+        self.draw_door(offset_x, offset_y);
+        self.draw_key(offset_x, offset_y);
+        // Draw agent, on top of other sprites
+        self.draw_agent(offset_x, offset_y);
+        self.draw_enemies(offset_x, offset_y);
+    }
+
+    // The draw steps are split so that the multi view can draw all games
+    // one layer at a time, keeping consecutive draws on the same texture
+
+    pub fn draw_background(&self, offset_x: f32, offset_y: f32) {
+        let resources = &self.resources;
+        let (w, h) = self.draw_size();
 
         // Draw level background image
         let background_tint = match self.is_complete {
@@ -169,35 +376,141 @@ impl Game {
             },
         );
 
-        // Draw door
-        if !self.is_key_collected {
+        // Ice isn't part of the background image
+        if is_tile_layer(&resources.lvl_map, LAYER_ICE) {
             resources
                 .lvl_map
-                .draw_tiles(LAYER_DOOR, Rect::new(offset_x, offset_y, w, h), None);
+                .draw_tiles(LAYER_ICE, Rect::new(offset_x, offset_y, w, h), None);
         }
+    }
 
-        // Draw keys
-        if !self.is_key_collected {
-            draw_texture_ex(
-                textures.key_texture,
-                self.lvl.key.0 as f32 * scale_factor + offset_x,
-                self.lvl.key.1 as f32 * scale_factor + offset_y,
-                WHITE,
-                DrawTextureParams {
-                    dest_size: Some(Vec2::splat(scale_factor)),
-                    ..Default::default()
-                },
+    pub fn draw_door(&self, offset_x: f32, offset_y: f32) {
+        if self.is_key_collected {
+            return;
+        }
+
+        // Only the door tile, instead of walking the whole layer
+        let resources = &self.resources;
+        let scale_factor = UNIT_FRAME_SIZE * FRAME_SCALE;
+        let (x, y) = (self.lvl.door.0 as f32, self.lvl.door.1 as f32);
+        if !is_tile_layer(&resources.lvl_map, LAYER_DOOR) {
+            // Door placed as an object, there's no tile to draw
+            draw_rectangle(
+                x * scale_factor + offset_x,
+                y * scale_factor + offset_y,
+                scale_factor,
+                scale_factor,
+                BROWN,
             );
+            return;
         }
+        resources.lvl_map.draw_tiles(
+            LAYER_DOOR,
+            Rect::new(
+                x * scale_factor + offset_x,
+                y * scale_factor + offset_y,
+                scale_factor,
+                scale_factor,
+            ),
+            Rect::new(x, y, 1.0, 1.0),
+        );
+    }
 
-        // Debug print
-        // let f = format!("{}", f);
-        // draw_text(f.as_str(), 50.0, 50.0, 40.0, RED);
+    pub fn draw_key(&self, offset_x: f32, offset_y: f32) {
+        if self.is_key_collected {
+            return;
+        }
+
+        let textures = TEXTURES.get().unwrap();
+        let scale_factor = UNIT_FRAME_SIZE * FRAME_SCALE;
+        draw_texture_ex(
+            textures.key_texture,
+            self.lvl.key.0 as f32 * scale_factor + offset_x,
+            self.lvl.key.1 as f32 * scale_factor + offset_y,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(Vec2::splat(scale_factor)),
+                ..Default::default()
+            },
+        );
+    }
+
+    pub fn draw_agent(&self, offset_x: f32, offset_y: f32) {
+        if self.is_dead {
+            return;
+        }
+
+        self.agent
+            .draw(UNIT_FRAME_SIZE * FRAME_SCALE, offset_x, offset_y);
+    }
+
+    pub fn draw_enemies(&self, offset_x: f32, offset_y: f32) {
+        self.enemy_manager
+            .draw(UNIT_FRAME_SIZE * FRAME_SCALE, offset_x, offset_y);
+    }
+
+    /// Flat coloured room with the agent as a dot, for far zoomed out views
+    pub fn draw_low_detail(&self, offset_x: f32, offset_y: f32) {
+        let (w, h) = self.draw_size();
+        let color = match (self.is_complete, self.is_dead, self.is_key_collected) {
+            (true, _, _) => Color::from_rgba(116, 242, 145, 255),
+            (_, true, _) => Color::from_rgba(150, 50, 50, 255),
+            (_, _, true) => Color::from_rgba(200, 170, 60, 255),
+            _ => Color::from_rgba(70, 70, 80, 255),
+        };
+        draw_rectangle(offset_x, offset_y, w, h, color);
+
+        if !self.is_dead {
+            self.draw_dot(WHITE, offset_x, offset_y);
+        }
+    }
+
+    pub fn draw_size(&self) -> (f32, f32) {
+        let scale_factor = UNIT_FRAME_SIZE * FRAME_SCALE;
+        (
+            self.lvl.size.0 as f32 * scale_factor,
+            self.lvl.size.1 as f32 * scale_factor,
+        )
+    }
+
+    /// Translucent agent along with the path it took, drawn on top of another game
+    pub fn draw_ghost(&self, color: Color, offset_x: f32, offset_y: f32) {
+        let scale_factor = UNIT_FRAME_SIZE * FRAME_SCALE;
+        let center = |(x, y): (usize, usize)| {
+            (
+                (x as f32 + 0.5) * scale_factor + offset_x,
+                (y as f32 + 0.5) * scale_factor + offset_y,
+            )
+        };
+
+        for w in self.trail.windows(2) {
+            let (x1, y1) = center(w[0]);
+            let (x2, y2) = center(w[1]);
+            draw_line(
+                x1,
+                y1,
+                x2,
+                y2,
+                4.0,
+                Color::new(color.r, color.g, color.b, 0.5),
+            );
+        }
 
         if !self.is_dead {
-            // Draw agent, on top of other sprites
-            self.agent.draw(scale_factor, offset_x, offset_y);
+            let tint = Color::new(color.r, color.g, color.b, 0.6);
+            self.agent
+                .draw_tinted(tint, scale_factor, offset_x, offset_y);
         }
-        self.enemy_manager.draw(scale_factor, offset_x, offset_y);
+    }
+
+    pub fn draw_dot(&self, color: Color, offset_x: f32, offset_y: f32) {
+        let scale_factor = UNIT_FRAME_SIZE * FRAME_SCALE;
+        let (x, y) = self.agent.pos;
+        draw_circle(
+            (x as f32 + 0.5) * scale_factor + offset_x,
+            (y as f32 + 0.5) * scale_factor + offset_y,
+            scale_factor * 0.15,
+            color,
+        );
     }
 }
@@ -0,0 +1,620 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+
+use ::rand::rngs::StdRng;
+use ::rand::seq::SliceRandom;
+use ::rand::{Rng, SeedableRng};
+use macroquad::prelude::*;
+
+use crate::export::{TileLayer, TiledLevel, Tileset};
+use crate::ff::FF;
+use crate::level::{GameItem, LevelInfo};
+use crate::*;
+
+// Tileset gids (1 based) used when writing a room, same as the shipped levels
+const GID_WALL: u32 = 1;
+const GID_WALL_FRONT: u32 = 2;
+const GID_FLOOR: u32 = 29;
+const GID_DOOR: u32 = 19;
+const GID_KEY: u32 = 31;
+const GID_PLAYER: u32 = 49;
+const GID_SMALL_SPIKE: u32 = 102;
+const GID_CRAB_HORIZONTAL: u32 = 78;
+const GID_CRAB_VERTICAL: u32 = 89;
+
+// x, y, w, h of a carved out room, in tiles
+type RoomRect = (usize, usize, usize, usize);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Algorithm {
+    // Rectangular rooms joined by one tile wide corridors
+    Rooms,
+    // Recursive backtracker maze, with loops at lower difficulties
+    Maze,
+}
+
+pub struct GeneratorConfig {
+    pub size: (usize, usize),
+    /// `0.0` to `1.0`, more spikes and crabs and fewer loops as it goes up
+    pub difficulty: f32,
+    pub seed: u64,
+    pub algorithm: Algorithm,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Crab {
+    pub pos: (usize, usize),
+    pub is_vertical: bool,
+}
+
+impl Crab {
+    /// Whether the crab patrols the way its tile does by default, which is
+    /// all a room can write back
+    fn is_plain(item: &GameItem) -> bool {
+        let is_default_direction = matches!(
+            (item.property("axis"), item.property("direction")),
+            (None | Some("horizontal"), None | Some("positive"))
+                | (Some("vertical"), Some("negative"))
+        );
+        is_default_direction
+            && ["speed", "min", "max", "path"]
+                .iter()
+                .all(|name| item.property(name).is_none())
+    }
+}
+
+/// A generated level, laid out like the `LevelInfo` of a Tiled map
+#[derive(Clone, PartialEq, Eq)]
+pub struct Room {
+    pub size: (usize, usize),
+    // Row major, `size.0 * size.1` tiles
+    pub walls: Vec<bool>,
+    pub door: (usize, usize),
+    pub key: (usize, usize),
+    pub agent: (usize, usize),
+    pub spikes: Vec<(usize, usize)>,
+    pub crabs: Vec<Crab>,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            size: GENERATOR_SIZE,
+            difficulty: GENERATOR_DIFFICULTY,
+            seed: 0,
+            algorithm: Algorithm::Rooms,
+        }
+    }
+}
+
+/// Generates a room that's always solvable, the same config (and seed)
+/// always gives the same room
+pub fn generate(config: &GeneratorConfig) -> Room {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let difficulty = config.difficulty.clamp(0.0, 1.0);
+    let size = (config.size.0.max(7), config.size.1.max(7));
+
+    let (walls, rooms) = match config.algorithm {
+        Algorithm::Rooms => carve_rooms(size, difficulty, &mut rng),
+        Algorithm::Maze => {
+            let walls = carve_maze(size, difficulty, &mut rng);
+            // As many patrols to pick from as there would be rooms
+            let mut corridors = maze_corridors(&walls, size);
+            corridors.shuffle(&mut rng);
+            corridors.truncate(num_rooms(size));
+            (walls, corridors)
+        }
+    };
+    let mut room = Room {
+        size,
+        walls,
+        door: (0, 0),
+        key: (0, 0),
+        agent: (0, 0),
+        spikes: Vec::new(),
+        crabs: Vec::new(),
+    };
+
+    // Key as far as possible from the spawn, door as far as possible from the key
+    let floor = room.floor_tiles();
+    room.agent = *floor.choose(&mut rng).unwrap();
+    room.key = room.farthest_from(room.agent);
+    let exit = room.farthest_from(room.key);
+    room.door = room.carve_exit(exit);
+
+    room.place_crabs(&rooms, difficulty, &mut rng);
+    room.place_spikes(difficulty, &mut rng);
+    assert!(room.is_solvable(), "Generated an unsolvable room");
+
+    room
+}
+
+/// Rooms placed at random without overlapping, each joined to the next one
+/// by an L shaped corridor. Returns the walls and the room rects
+fn carve_rooms(
+    size: (usize, usize),
+    difficulty: f32,
+    rng: &mut StdRng,
+) -> (Vec<bool>, Vec<RoomRect>) {
+    let mut walls = vec![true; size.0 * size.1];
+    let mut rooms: Vec<RoomRect> = Vec::new();
+    let max_w = (size.0 / 3).max(3);
+    let max_h = (size.1 / 3).max(3);
+    let num_rooms = num_rooms(size);
+
+    for _ in 0..num_rooms * 10 {
+        if rooms.len() >= num_rooms {
+            break;
+        }
+        let w = rng.gen_range(3..=max_w);
+        let h = rng.gen_range(3..=max_h);
+        let x = rng.gen_range(1..size.0 - w);
+        let y = rng.gen_range(1..size.1 - h);
+
+        // Keep a wall between rooms
+        let is_overlapping = rooms.iter().any(|&(rx, ry, rw, rh)| {
+            x < rx + rw + 1 && rx < x + w + 1 && y < ry + rh + 1 && ry < y + h + 1
+        });
+        if !is_overlapping {
+            rooms.push((x, y, w, h));
+        }
+    }
+    rooms.sort_by_key(|(x, y, w, h)| (x + w / 2, y + h / 2));
+
+    for (x, y, w, h) in rooms.iter() {
+        for j in *y..y + h {
+            for i in *x..x + w {
+                walls[j * size.0 + i] = false;
+            }
+        }
+    }
+
+    let center = |(x, y, w, h): RoomRect| (x + w / 2, y + h / 2);
+    for pair in rooms.windows(2) {
+        carve_corridor(&mut walls, size, center(pair[0]), center(pair[1]));
+    }
+
+    // Extra corridors make loops, so there's more than one way around
+    let num_loops = ((1.0 - difficulty) * rooms.len() as f32 / 2.0) as usize;
+    for _ in 0..num_loops {
+        let a = *rooms.choose(rng).unwrap();
+        let b = *rooms.choose(rng).unwrap();
+        carve_corridor(&mut walls, size, center(a), center(b));
+    }
+
+    (walls, rooms)
+}
+
+fn carve_corridor(
+    walls: &mut [bool],
+    size: (usize, usize),
+    from: (usize, usize),
+    to: (usize, usize),
+) {
+    for x in from.0.min(to.0)..=from.0.max(to.0) {
+        walls[from.1 * size.0 + x] = false;
+    }
+    for y in from.1.min(to.1)..=from.1.max(to.1) {
+        walls[y * size.0 + to.0] = false;
+    }
+}
+
+fn num_rooms(size: (usize, usize)) -> usize {
+    (size.0 * size.1 / 60).max(2)
+}
+
+/// Cells sit on odd tiles, walls between them are knocked down as the
+/// backtracker visits them
+fn carve_maze(size: (usize, usize), difficulty: f32, rng: &mut StdRng) -> Vec<bool> {
+    let mut walls = vec![true; size.0 * size.1];
+    let cells = ((size.0 - 1) / 2, (size.1 - 1) / 2);
+    let mut is_visited = vec![false; cells.0 * cells.1];
+    let to_tile = |(cx, cy): (usize, usize)| (cx * 2 + 1, cy * 2 + 1);
+
+    let start = (rng.gen_range(0..cells.0), rng.gen_range(0..cells.1));
+    let mut stack = vec![start];
+    is_visited[start.1 * cells.0 + start.0] = true;
+    let (x, y) = to_tile(start);
+    walls[y * size.0 + x] = false;
+
+    while let Some(&cell) = stack.last() {
+        let mut next: Vec<(usize, usize)> = maze_neighbours(cell, cells)
+            .into_iter()
+            .filter(|(cx, cy)| !is_visited[cy * cells.0 + cx])
+            .collect();
+        next.shuffle(rng);
+        let Some(&(nx, ny)) = next.first() else {
+            stack.pop();
+            continue;
+        };
+
+        is_visited[ny * cells.0 + nx] = true;
+        let (x0, y0) = to_tile(cell);
+        let (x1, y1) = to_tile((nx, ny));
+        walls[y1 * size.0 + x1] = false;
+        walls[(y0 + y1) / 2 * size.0 + (x0 + x1) / 2] = false;
+        stack.push((nx, ny));
+    }
+
+    // Knock down a few more walls between cells to make loops
+    let num_loops = ((1.0 - difficulty) * (cells.0 * cells.1) as f32 / 8.0) as usize;
+    for _ in 0..num_loops {
+        let cell = (rng.gen_range(0..cells.0), rng.gen_range(0..cells.1));
+        if let Some(&other) = maze_neighbours(cell, cells).choose(rng) {
+            let (x0, y0) = to_tile(cell);
+            let (x1, y1) = to_tile(other);
+            walls[(y0 + y1) / 2 * size.0 + (x0 + x1) / 2] = false;
+        }
+    }
+
+    walls
+}
+
+/// Straight runs of floor at least three tiles long with a wall at both
+/// ends, as one tile wide or high rects for the crabs to patrol
+fn maze_corridors(walls: &[bool], size: (usize, usize)) -> Vec<RoomRect> {
+    let is_floor = |x: usize, y: usize| !walls[y * size.0 + x];
+    let mut corridors = Vec::new();
+    for y in 0..size.1 {
+        let mut x = 0;
+        while x < size.0 {
+            let start = x;
+            while x < size.0 && is_floor(x, y) {
+                x += 1;
+            }
+            if x - start >= 3 {
+                corridors.push((start, y, x - start, 1));
+            }
+            x += 1;
+        }
+    }
+    for x in 0..size.0 {
+        let mut y = 0;
+        while y < size.1 {
+            let start = y;
+            while y < size.1 && is_floor(x, y) {
+                y += 1;
+            }
+            if y - start >= 3 {
+                corridors.push((x, start, 1, y - start));
+            }
+            y += 1;
+        }
+    }
+
+    corridors
+}
+
+fn maze_neighbours((x, y): (usize, usize), cells: (usize, usize)) -> Vec<(usize, usize)> {
+    [(1, 0), (-1, 0), (0, 1), (0, -1)]
+        .iter()
+        .filter_map(|(dx, dy)| Some((x.checked_add_signed(*dx)?, y.checked_add_signed(*dy)?)))
+        .filter(|(x, y)| *x < cells.0 && *y < cells.1)
+        .collect()
+}
+
+impl Room {
+    /// The loaded level as a room, for editing. A room only holds walls,
+    /// small spikes and plain crabs, levels using anything else are refused
+    /// rather than losing it. The error names what's in the way
+    pub fn from_level(lvl: &LevelInfo) -> Result<Self, String> {
+        let mut unsupported = Vec::new();
+        let mut add = |feature: &'static str| {
+            if !unsupported.contains(&feature) {
+                unsupported.push(feature);
+            }
+        };
+        if lvl.ice.iter().any(|is_ice| *is_ice) {
+            add("ice");
+        }
+        if lvl.spawn_candidates.len() > 1 || lvl.key_candidates.len() > 1 {
+            add("spawn or key candidates");
+        }
+        for spike in lvl.spikes.iter() {
+            match (spike.property("enemy"), spike.property("sprite")) {
+                (None | Some("spike"), Some("small")) => {}
+                (Some("timed_spike"), _) => add("timed spikes"),
+                _ => add("large or hidden spikes"),
+            }
+        }
+        for enemy in lvl.enemies.iter() {
+            match enemy.property("enemy").unwrap_or("crab") {
+                "crab" if Crab::is_plain(enemy) => {}
+                "crab" => add("crab speeds, bounds, paths or directions"),
+                "chaser" => add("chasers"),
+                "turret" => add("turrets"),
+                _ => add("spikes on the enemies layer"),
+            }
+        }
+        if !unsupported.is_empty() {
+            return Err(format!(
+                "The level uses {}, which the editor can't keep",
+                unsupported.join(", ")
+            ));
+        }
+
+        let crabs = lvl
+            .enemies
+            .iter()
+            .filter(|e| e.property("enemy").unwrap_or("crab") == "crab")
+            .map(|e| Crab {
+                pos: e.pos,
+                is_vertical: e.property("axis") == Some("vertical"),
+            })
+            .collect();
+
+        Ok(Self {
+            size: lvl.size,
+            walls: lvl.walls.clone(),
+            door: lvl.door,
+            key: lvl.key,
+            agent: lvl.agent,
+            spikes: lvl.spikes.iter().map(|s| s.pos).collect(),
+            crabs,
+        })
+    }
+
+    pub fn is_wall(&self, (x, y): (usize, usize)) -> bool {
+        if x >= self.size.0 || y >= self.size.1 {
+            return true;
+        }
+
+        self.walls[y * self.size.0 + x]
+    }
+
+    fn floor_tiles(&self) -> Vec<(usize, usize)> {
+        (0..self.size.1)
+            .flat_map(|y| (0..self.size.0).map(move |x| (x, y)))
+            .filter(|pos| !self.is_wall(*pos))
+            .collect()
+    }
+
+    /// Walls with the spikes added, spikes never move so they block like walls
+    fn obstacles(&self) -> Vec<bool> {
+        let mut obstacles = self.walls.clone();
+        self.spikes
+            .iter()
+            .for_each(|(x, y)| obstacles[y * self.size.0 + x] = true);
+
+        obstacles
+    }
+
+    /// Door reachable from the key and the key from the spawn, around walls
+    /// and spikes. Crabs are only placed where they can be timed, so they
+    /// aren't part of the check
+    pub fn is_solvable(&self) -> bool {
+        let obstacles = self.obstacles();
+        let to_door = FF::with_walls(&self.door, &self.size, obstacles.clone()).solve();
+        let to_key = FF::with_walls(&self.key, &self.size, obstacles).solve();
+
+        to_door.contains_key(&self.key) && to_key.contains_key(&self.agent)
+    }
+
+    fn farthest_from(&self, start: (usize, usize)) -> (usize, usize) {
+        let mut dist = vec![usize::MAX; self.size.0 * self.size.1];
+        let mut q = VecDeque::from([start]);
+        let mut farthest = start;
+        dist[start.1 * self.size.0 + start.0] = 0;
+
+        while let Some((x, y)) = q.pop_front() {
+            farthest = (x, y);
+            let d = dist[y * self.size.0 + x];
+            for n in maze_neighbours((x, y), self.size) {
+                if dist[n.1 * self.size.0 + n.0] == usize::MAX && !self.is_wall(n) {
+                    dist[n.1 * self.size.0 + n.0] = d + 1;
+                    q.push_back(n);
+                }
+            }
+        }
+
+        farthest
+    }
+
+    /// Digs a straight tunnel from `pos` to the closest edge of the room,
+    /// the door goes where it comes out
+    fn carve_exit(&mut self, (x, y): (usize, usize)) -> (usize, usize) {
+        let (w, h) = self.size;
+        let distances = [x, w - 1 - x, y, h - 1 - y];
+        let (dx, dy) = match distances.iter().enumerate().min_by_key(|(_, d)| **d) {
+            Some((0, _)) => (-1, 0),
+            Some((1, _)) => (1, 0),
+            Some((2, _)) => (0, -1),
+            _ => (0, 1),
+        };
+
+        let mut pos = (x, y);
+        loop {
+            self.walls[pos.1 * w + pos.0] = false;
+            match (
+                pos.0.checked_add_signed(dx).filter(|x| *x < w),
+                pos.1.checked_add_signed(dy).filter(|y| *y < h),
+            ) {
+                (Some(nx), Some(ny)) => pos = (nx, ny),
+                _ => return pos,
+            }
+        }
+    }
+
+    /// Crabs patrol across rooms, on rows or columns closed off by walls at
+    /// both ends so they never walk out into a corridor
+    fn place_crabs(&mut self, rooms: &[RoomRect], difficulty: f32, rng: &mut StdRng) {
+        let num_crabs = (difficulty * rooms.len() as f32).round() as usize;
+        let reserved = [self.agent, self.key, self.door];
+        for &(x, y, w, h) in rooms.choose_multiple(rng, num_crabs) {
+            // Corridors only fit a crab patrolling along them
+            let is_vertical = match (w, h) {
+                (1, _) => true,
+                (_, 1) => false,
+                _ => rng.gen_bool(0.5),
+            };
+            let lines: Vec<(usize, usize)> = match is_vertical {
+                true => (x..x + w).map(|i| (i, y)).collect(),
+                false => (y..y + h).map(|j| (x, j)).collect(),
+            };
+            let is_closed = |(i, j): (usize, usize)| match is_vertical {
+                true => self.is_wall((i, j - 1)) && self.is_wall((i, j + h)),
+                false => self.is_wall((i - 1, j)) && self.is_wall((i + w, j)),
+            };
+            let Some(&(i, j)) = lines
+                .iter()
+                .filter(|pos| is_closed(**pos))
+                .collect::<Vec<_>>()
+                .choose(rng)
+                .copied()
+            else {
+                continue;
+            };
+
+            // Start somewhere along the line, away from the spawn, key and door
+            let start = match is_vertical {
+                true => (i, y + rng.gen_range(0..h)),
+                false => (x + rng.gen_range(0..w), j),
+            };
+            if !reserved.contains(&start) {
+                self.crabs.push(Crab {
+                    pos: start,
+                    is_vertical,
+                });
+            }
+        }
+    }
+
+    /// Spikes are dropped on random floor tiles, any that would make the
+    /// room unsolvable are taken back out
+    fn place_spikes(&mut self, difficulty: f32, rng: &mut StdRng) {
+        let floor = self.floor_tiles();
+        let num_spikes = (difficulty * floor.len() as f32 / 15.0) as usize;
+        let mut reserved = vec![self.agent, self.key, self.door];
+        reserved.extend(self.crabs.iter().map(|c| c.pos));
+
+        for pos in floor.choose_multiple(rng, num_spikes * 2) {
+            if self.spikes.len() >= num_spikes {
+                break;
+            }
+            if reserved.contains(pos) {
+                continue;
+            }
+
+            self.spikes.push(*pos);
+            if !self.is_solvable() {
+                self.spikes.pop();
+            }
+        }
+    }
+
+    /// The room as Tiled tile layers, in the layer layout of the shipped levels
+    pub fn to_tiled(&self) -> TiledLevel {
+        let (w, h) = self.size;
+        let tile_layer = |name: &str, gid: u32, tiles: &[(usize, usize)], is_visible: bool| {
+            let mut data = vec![0; w * h];
+            tiles.iter().for_each(|(x, y)| data[y * w + x] = gid);
+            TileLayer {
+                name: name.to_string(),
+                data,
+                is_visible,
+            }
+        };
+
+        // Walls facing the floor get the shaded front tile
+        let walls = (0..w * h)
+            .map(
+                |i| match (self.walls[i], self.is_wall((i % w, i / w + 1))) {
+                    (false, _) => 0,
+                    (true, true) => GID_WALL,
+                    (true, false) => GID_WALL_FRONT,
+                },
+            )
+            .collect();
+        let mut enemies = vec![0; w * h];
+        for crab in self.crabs.iter() {
+            enemies[crab.pos.1 * w + crab.pos.0] = match crab.is_vertical {
+                true => GID_CRAB_VERTICAL,
+                false => GID_CRAB_HORIZONTAL,
+            };
+        }
+
+        TiledLevel {
+            size: self.size,
+            layers: vec![
+                TileLayer {
+                    name: LAYER_BACKGROUND.to_string(),
+                    data: vec![GID_FLOOR; w * h],
+                    is_visible: true,
+                },
+                TileLayer {
+                    name: LAYER_WALLS.to_string(),
+                    data: walls,
+                    is_visible: true,
+                },
+                TileLayer {
+                    name: LAYER_ENEMIES.to_string(),
+                    data: enemies,
+                    is_visible: true,
+                },
+                tile_layer(LAYER_SPIKES, GID_SMALL_SPIKE, &self.spikes, true),
+                tile_layer(LAYER_DOOR, GID_DOOR, &[self.door], false),
+                tile_layer(LAYER_KEYS, GID_KEY, &[self.key], false),
+                tile_layer(LAYER_PLAYER, GID_PLAYER, &[self.agent], false),
+            ],
+            object_layers: Vec::new(),
+            tilesets: vec![Tileset::default()],
+        }
+    }
+
+    /// Background image of the room, the floor and walls drawn from the tileset
+    pub fn render_background(&self) -> Image {
+        let bytes = fs::read(TILESET_PATH).expect("Failed to read the tileset");
+        let tileset = Image::from_file_with_format(&bytes, Some(ImageFormat::Png));
+        let tile = UNIT_FRAME_SIZE as u32;
+        let columns = tileset.width() as u32 / tile;
+        let (w, h) = (self.size.0 as u32, self.size.1 as u32);
+        let mut image = Image::gen_image_color((w * tile) as u16, (h * tile) as u16, BLACK);
+
+        let level = self.to_tiled();
+        for layer in [LAYER_BACKGROUND, LAYER_WALLS] {
+            let data = &level.layer(layer).unwrap().data;
+            for (i, gid) in data.iter().enumerate().filter(|(_, g)| **g > 0) {
+                let (sx, sy) = ((gid - 1) % columns * tile, (gid - 1) / columns * tile);
+                let (dx, dy) = (i as u32 % w * tile, i as u32 / w * tile);
+                for y in 0..tile {
+                    for x in 0..tile {
+                        let color = tileset.get_pixel(sx + x, sy + y);
+                        if color.a > 0.0 {
+                            image.set_pixel(dx + x, dy + y, color);
+                        }
+                    }
+                }
+            }
+        }
+
+        image
+    }
+}
+
+/// Saves the room as `<out_path>.json`, with its background `<out_path>.png`
+pub fn save_room(room: &Room, out_path: &str) -> io::Result<()> {
+    fs::write(format!("{}.json", out_path), room.to_tiled().to_json())?;
+    recorder::save_png(&room.render_background(), &format!("{}.png", out_path))
+}
+
+/// Generates a room and saves it as `<out_path>.json` along with its
+/// background image `<out_path>.png`
+pub fn run(out_path: &str, config: &GeneratorConfig) {
+    let room = generate(config);
+    if let Err(e) = save_room(&room, out_path) {
+        eprintln!("Failed to save the room to {}: {}", out_path, e);
+        return;
+    }
+    println!(
+        "Saved a {}x{} room to {}.json, spawn {:?} key {:?} door {:?}, {} spikes, {} crabs",
+        room.size.0,
+        room.size.1,
+        out_path,
+        room.agent,
+        room.key,
+        room.door,
+        room.spikes.len(),
+        room.crabs.len()
+    );
+}
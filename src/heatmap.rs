@@ -0,0 +1,94 @@
+use macroquad::prelude::*;
+
+/// Per tile counter, drawn as a translucent overlay on top of a room
+#[derive(Clone)]
+pub struct Heatmap {
+    size: (usize, usize),
+    counts: Vec<u32>,
+}
+
+impl Heatmap {
+    pub fn new(size: (usize, usize)) -> Self {
+        Self {
+            size,
+            counts: vec![0; size.0 * size.1],
+        }
+    }
+
+    pub fn add(&mut self, (x, y): (usize, usize)) {
+        if x >= self.size.0 || y >= self.size.1 {
+            return;
+        }
+
+        self.counts[y * self.size.0 + x] += 1;
+    }
+
+    pub fn get(&self, (x, y): (usize, usize)) -> u32 {
+        if x >= self.size.0 || y >= self.size.1 {
+            return 0;
+        }
+
+        self.counts[y * self.size.0 + x]
+    }
+
+    pub fn total(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+
+    pub fn max(&self) -> u32 {
+        self.counts.iter().copied().max().unwrap_or(0)
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.iter_mut().for_each(|c| *c = 0);
+    }
+
+    pub fn draw(&self, color: Color, scale_factor: f32, offset_x: f32, offset_y: f32) {
+        // Keep the least visited tiles visible
+        self.draw_with(scale_factor, offset_x, offset_y, |t| {
+            Color::new(color.r, color.g, color.b, 0.2 + 0.6 * t)
+        });
+    }
+
+    /// Draws the counts as a blue -> green -> yellow -> red ramp
+    pub fn draw_ramp(&self, scale_factor: f32, offset_x: f32, offset_y: f32) {
+        self.draw_with(scale_factor, offset_x, offset_y, |t| {
+            let (r, g, b) = match t {
+                t if t < 0.33 => (0.0, t / 0.33, 1.0 - t / 0.33),
+                t if t < 0.66 => ((t - 0.33) / 0.33, 1.0, 0.0),
+                t => (1.0, 1.0 - (t - 0.66) / 0.34, 0.0),
+            };
+            Color::new(r, g, b, 0.55)
+        });
+    }
+
+    fn draw_with(
+        &self,
+        scale_factor: f32,
+        offset_x: f32,
+        offset_y: f32,
+        color_fn: impl Fn(f32) -> Color,
+    ) {
+        let max = self.max();
+        if max == 0 {
+            return;
+        }
+
+        for x in 0..self.size.0 {
+            for y in 0..self.size.1 {
+                let count = self.get((x, y));
+                if count == 0 {
+                    continue;
+                }
+
+                draw_rectangle(
+                    x as f32 * scale_factor + offset_x,
+                    y as f32 * scale_factor + offset_y,
+                    scale_factor,
+                    scale_factor,
+                    color_fn(count as f32 / max as f32),
+                );
+            }
+        }
+    }
+}
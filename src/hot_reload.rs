@@ -0,0 +1,59 @@
+use std::fs;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::resources::level_paths;
+use crate::*;
+
+/// Polls the modified time of the files of every level, so a room can be
+/// edited in Tiled while the simulation runs
+pub struct LevelWatcher {
+    paths: Vec<&'static str>,
+    // Of the files last loaded, see `loaded`
+    mtimes: Vec<Option<SystemTime>>,
+    polled_mtimes: Vec<Option<SystemTime>>,
+    last_poll: Instant,
+}
+
+impl Default for LevelWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LevelWatcher {
+    pub fn new() -> Self {
+        let paths: Vec<&'static str> = level_paths()
+            .flat_map(|(map_path, background_path)| [map_path, background_path])
+            .collect();
+        let mtimes: Vec<Option<SystemTime>> =
+            paths.iter().map(|p| LevelWatcher::mtime(p)).collect();
+        Self {
+            polled_mtimes: mtimes.clone(),
+            mtimes,
+            paths,
+            last_poll: Instant::now(),
+        }
+    }
+
+    fn mtime(path: &str) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// True if any of the files changed since they were last loaded,
+    /// checked every `HOT_RELOAD_INTERVAL` seconds. Keeps returning true
+    /// until `loaded` is called, so a failed reload is retried
+    pub fn poll(&mut self) -> bool {
+        if self.last_poll.elapsed() < Duration::from_secs_f32(HOT_RELOAD_INTERVAL) {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        self.polled_mtimes = self.paths.iter().map(|p| LevelWatcher::mtime(p)).collect();
+        self.polled_mtimes != self.mtimes
+    }
+
+    /// The files seen by the last `poll` were reloaded
+    pub fn loaded(&mut self) {
+        self.mtimes = self.polled_mtimes.clone();
+    }
+}
@@ -0,0 +1,437 @@
+use std::fs;
+
+use macroquad::prelude::*;
+
+use crate::agent::AgentCommand;
+use crate::*;
+
+/// Everything the simulation does on a key press, see `KEY_BINDINGS` for
+/// the default keys
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Pause,
+    ToggleUi,
+    Restart,
+    SlowMode,
+    ToggleAi,
+    FrameSkip,
+    EditMode,
+    FollowAgent,
+    PlayMode,
+    UseDemo,
+    StepFrame,
+    StepGeneration,
+    Help,
+    Quit,
+    PanLeft,
+    PanRight,
+    PanUp,
+    PanDown,
+    ZoomIn,
+    ZoomOut,
+    MoveUp,
+    MoveLeft,
+    MoveDown,
+    MoveRight,
+    Wait,
+    MoveUpLeft,
+    MoveUpRight,
+    MoveDownLeft,
+    MoveDownRight,
+}
+
+/// Keys bound to each action, the defaults are overridden by the ones in
+/// `INPUT_BINDINGS_PATH`
+#[derive(Clone, PartialEq, Debug)]
+pub struct InputMap {
+    bindings: Vec<(Action, Vec<KeyCode>)>,
+}
+
+impl Action {
+    pub const ALL: [Action; 29] = [
+        Action::Pause,
+        Action::ToggleUi,
+        Action::Restart,
+        Action::SlowMode,
+        Action::ToggleAi,
+        Action::FrameSkip,
+        Action::EditMode,
+        Action::FollowAgent,
+        Action::PlayMode,
+        Action::UseDemo,
+        Action::StepFrame,
+        Action::StepGeneration,
+        Action::Help,
+        Action::Quit,
+        Action::PanLeft,
+        Action::PanRight,
+        Action::PanUp,
+        Action::PanDown,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::MoveUp,
+        Action::MoveLeft,
+        Action::MoveDown,
+        Action::MoveRight,
+        Action::Wait,
+        Action::MoveUpLeft,
+        Action::MoveUpRight,
+        Action::MoveDownLeft,
+        Action::MoveDownRight,
+    ];
+
+    /// Name in the bindings file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Pause => "pause",
+            Action::ToggleUi => "toggle_ui",
+            Action::Restart => "restart",
+            Action::SlowMode => "slow_mode",
+            Action::ToggleAi => "toggle_ai",
+            Action::FrameSkip => "frame_skip",
+            Action::EditMode => "edit_mode",
+            Action::FollowAgent => "follow_agent",
+            Action::PlayMode => "play_mode",
+            Action::UseDemo => "use_demo",
+            Action::StepFrame => "step_frame",
+            Action::StepGeneration => "step_generation",
+            Action::Help => "help",
+            Action::Quit => "quit",
+            Action::PanLeft => "pan_left",
+            Action::PanRight => "pan_right",
+            Action::PanUp => "pan_up",
+            Action::PanDown => "pan_down",
+            Action::ZoomIn => "zoom_in",
+            Action::ZoomOut => "zoom_out",
+            Action::MoveUp => "move_up",
+            Action::MoveLeft => "move_left",
+            Action::MoveDown => "move_down",
+            Action::MoveRight => "move_right",
+            Action::Wait => "wait",
+            Action::MoveUpLeft => "move_up_left",
+            Action::MoveUpRight => "move_up_right",
+            Action::MoveDownLeft => "move_down_left",
+            Action::MoveDownRight => "move_down_right",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Pause => "Pause the simulation",
+            Action::ToggleUi => "Show the control menu",
+            Action::Restart => "Restart the simulation",
+            Action::SlowMode => "Slow mode",
+            Action::ToggleAi => "Enable the AI",
+            Action::FrameSkip => "Run 10x faster",
+            Action::EditMode => "Level editor",
+            Action::FollowAgent => "Follow the agent",
+            Action::PlayMode => "Play mode",
+            Action::UseDemo => "Use the run played as a demo",
+            Action::StepFrame => "Step one frame",
+            Action::StepGeneration => "Step one generation",
+            Action::Help => "Show this help",
+            Action::Quit => "Quit",
+            Action::PanLeft => "Pan left",
+            Action::PanRight => "Pan right",
+            Action::PanUp => "Pan up",
+            Action::PanDown => "Pan down",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::MoveUp => "Play: move up",
+            Action::MoveLeft => "Play: move left",
+            Action::MoveDown => "Play: move down",
+            Action::MoveRight => "Play: move right",
+            Action::Wait => "Play: wait",
+            Action::MoveUpLeft => "Play: move up left",
+            Action::MoveUpRight => "Play: move up right",
+            Action::MoveDownLeft => "Play: move down left",
+            Action::MoveDownRight => "Play: move down right",
+        }
+    }
+
+    /// Agent move of the play mode actions
+    pub fn command(&self) -> Option<AgentCommand> {
+        match self {
+            Action::MoveUp => Some(AgentCommand::Top),
+            Action::MoveLeft => Some(AgentCommand::Left),
+            Action::MoveDown => Some(AgentCommand::Bottom),
+            Action::MoveRight => Some(AgentCommand::Right),
+            Action::Wait => Some(AgentCommand::Stay),
+            Action::MoveUpLeft => Some(AgentCommand::TopLeft),
+            Action::MoveUpRight => Some(AgentCommand::TopRight),
+            Action::MoveDownLeft => Some(AgentCommand::BottomLeft),
+            Action::MoveDownRight => Some(AgentCommand::BottomRight),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|a| a.name() == name)
+    }
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputMap {
+    /// The default bindings of `KEY_BINDINGS`
+    pub fn new() -> Self {
+        Self {
+            bindings: Action::ALL
+                .into_iter()
+                .map(|action| {
+                    let keys = KEY_BINDINGS
+                        .iter()
+                        .find(|(a, _)| *a == action)
+                        .map(|(_, keys)| keys.to_vec())
+                        .unwrap_or_default();
+                    (action, keys)
+                })
+                .collect(),
+        }
+    }
+
+    /// Defaults overridden by the bindings file, if there is one
+    pub fn load(path: &str) -> Self {
+        match fs::read_to_string(path) {
+            Ok(text) => InputMap::parse(&text),
+            Err(_) => InputMap::new(),
+        }
+    }
+
+    /// Reads `action = Key, Key` lines over the defaults, `#` starts a
+    /// comment. Unknown actions and keys are skipped with a warning, keys
+    /// bound to more than one action are warned about
+    pub fn parse(text: &str) -> Self {
+        let mut map = InputMap::new();
+        for line in text.lines().map(|l| l.split('#').next().unwrap().trim()) {
+            let Some((name, keys)) = line.split_once('=') else {
+                continue;
+            };
+            let Some(action) = Action::from_name(name.trim()) else {
+                eprintln!("Unknown input action {}", name.trim());
+                continue;
+            };
+            let keys = keys
+                .split(',')
+                .map(str::trim)
+                .filter(|k| !k.is_empty())
+                .filter_map(|k| {
+                    let key = parse_key(k);
+                    if key.is_none() {
+                        eprintln!("Unknown key {} for {}", k, action.name());
+                    }
+                    key
+                })
+                .collect();
+            map.set_keys(action, keys);
+        }
+
+        for (action, keys) in map.bindings.iter() {
+            for key in keys {
+                if let Some(other) = map.action_of(*key).filter(|a| a != action) {
+                    eprintln!(
+                        "{:?} is bound to both {} and {}",
+                        key,
+                        other.name(),
+                        action.name()
+                    );
+                }
+            }
+        }
+
+        map
+    }
+
+    pub fn save(&self, path: &str) {
+        if let Err(e) = fs::write(path, self.to_text()) {
+            eprintln!("Failed to save the key bindings to {}: {}", path, e);
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        self.bindings
+            .iter()
+            .map(|(action, _)| format!("{} = {}\n", action.name(), self.keys_text(*action)))
+            .collect()
+    }
+
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, keys)| keys.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Keys of the action for display, like `W, Up`
+    pub fn keys_text(&self, action: Action) -> String {
+        self.keys(action)
+            .iter()
+            .map(|k| format!("{:?}", k))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// First action the key is bound to
+    pub fn action_of(&self, key: KeyCode) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|(action, _)| *action)
+    }
+
+    /// Replaces the keys of the action
+    pub fn set_keys(&mut self, action: Action, keys: Vec<KeyCode>) {
+        if let Some((_, bound)) = self.bindings.iter_mut().find(|(a, _)| *a == action) {
+            *bound = keys;
+        }
+    }
+
+    /// Adds a key to the action, unless another action has it already.
+    /// That action is returned then, and nothing is bound
+    pub fn bind(&mut self, action: Action, key: KeyCode) -> Result<(), Action> {
+        match self.action_of(key) {
+            Some(other) if other != action => return Err(other),
+            Some(_) => return Ok(()),
+            None => (),
+        }
+        if let Some((_, bound)) = self.bindings.iter_mut().find(|(a, _)| *a == action) {
+            bound.push(key);
+        }
+
+        Ok(())
+    }
+
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.keys(action).iter().any(|k| is_key_pressed(*k))
+    }
+
+    pub fn is_down(&self, action: Action) -> bool {
+        self.keys(action).iter().any(|k| is_key_down(*k))
+    }
+
+    /// Agent move picked this frame in play mode
+    pub fn pressed_command(&self) -> Option<AgentCommand> {
+        Action::ALL
+            .into_iter()
+            .filter(|a| self.is_pressed(*a))
+            .find_map(|a| a.command())
+    }
+}
+
+/// Keys that can be bound, by their `KeyCode` name
+const KEYS: [KeyCode; 103] = [
+    KeyCode::Space,
+    KeyCode::Apostrophe,
+    KeyCode::Comma,
+    KeyCode::Minus,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Semicolon,
+    KeyCode::Equal,
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::LeftBracket,
+    KeyCode::Backslash,
+    KeyCode::RightBracket,
+    KeyCode::GraveAccent,
+    KeyCode::Escape,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Right,
+    KeyCode::Left,
+    KeyCode::Down,
+    KeyCode::Up,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::CapsLock,
+    KeyCode::ScrollLock,
+    KeyCode::NumLock,
+    KeyCode::PrintScreen,
+    KeyCode::Pause,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Kp0,
+    KeyCode::Kp1,
+    KeyCode::Kp2,
+    KeyCode::Kp3,
+    KeyCode::Kp4,
+    KeyCode::Kp5,
+    KeyCode::Kp6,
+    KeyCode::Kp7,
+    KeyCode::Kp8,
+    KeyCode::Kp9,
+    KeyCode::KpDecimal,
+    KeyCode::KpDivide,
+    KeyCode::KpMultiply,
+    KeyCode::KpSubtract,
+    KeyCode::KpAdd,
+    KeyCode::KpEnter,
+    KeyCode::KpEqual,
+    KeyCode::LeftShift,
+    KeyCode::LeftControl,
+    KeyCode::LeftAlt,
+    KeyCode::RightShift,
+    KeyCode::RightControl,
+    KeyCode::RightAlt,
+    KeyCode::Menu,
+];
+
+/// Key by its `KeyCode` name, case insensitive
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    KEYS.into_iter()
+        .find(|k| format!("{:?}", k).eq_ignore_ascii_case(name))
+}
@@ -1,11 +1,21 @@
-use macroquad_tiled::Map;
+use std::collections::HashMap;
 
+use macroquad::texture::Texture2D;
+use macroquad_tiled::{Map, Property};
+use nanoserde::DeJson;
+
+use crate::enemy::parse_path;
 use crate::*;
 
+// The top bits of a gid flag flipped and rotated tiles
+const GID_MASK: u32 = 0x1FFF_FFFF;
+
 #[derive(Clone, Debug)]
 pub struct GameItem {
     pub pos: (usize, usize),
     pub value: u32,
+    /// Custom properties set on the tile in Tiled
+    pub properties: HashMap<String, String>,
 }
 
 #[derive(Clone)]
@@ -14,17 +24,42 @@ pub struct LevelInfo {
     pub key: (usize, usize),
     pub door: (usize, usize),
     pub agent: (usize, usize),
+    // Tiles the agent and the key can be placed on, when randomised
+    pub spawn_candidates: Vec<(usize, usize)>,
+    pub key_candidates: Vec<(usize, usize)>,
+    // Row major, `size.0 * size.1` tiles
+    pub walls: Vec<bool>,
+    // Slippery tiles, same layout as walls
+    pub ice: Vec<bool>,
     pub spikes: Vec<GameItem>,
     pub enemies: Vec<GameItem>,
 }
 
 impl LevelInfo {
-    pub fn new(map: &Map) -> Self {
+    /// Fails if the map is missing the door, key or player spawn, or has
+    /// an enemy path that can't be walked
+    pub fn new(map: &Map) -> Result<Self, String> {
         LevelInfo::parse_level(map)
     }
 
+    pub fn is_wall(&self, (x, y): (usize, usize)) -> bool {
+        if x >= self.size.0 || y >= self.size.1 {
+            return true;
+        }
+
+        self.walls[y * self.size.0 + x]
+    }
+
+    pub fn is_ice(&self, (x, y): (usize, usize)) -> bool {
+        if x >= self.size.0 || y >= self.size.1 {
+            return false;
+        }
+
+        self.ice[y * self.size.0 + x]
+    }
+
     fn get_one_item(w: u32, h: u32, layer: &str, map: &Map) -> Option<(usize, usize)> {
-        if !map.contains_layer(layer) {
+        if !is_tile_layer(map, layer) {
             return None;
         }
 
@@ -40,16 +75,34 @@ impl LevelInfo {
         None
     }
 
+    /// Every tile set in any of the layers, without duplicates
+    fn get_candidates(w: u32, h: u32, layers: &[&str], map: &Map) -> Vec<(usize, usize)> {
+        let mut candidates = Vec::new();
+        for layer in layers {
+            for item in LevelInfo::get_all_items(w, h, layer, map) {
+                if !candidates.contains(&item.pos) {
+                    candidates.push(item.pos);
+                }
+            }
+        }
+
+        candidates
+    }
+
     fn get_all_items(w: u32, h: u32, layer: &str, map: &Map) -> Vec<GameItem> {
         let mut items = Vec::new();
-        if !map.contains_layer(layer) {
+        if !is_tile_layer(map, layer) {
             return items;
         }
 
         for i in 0..w {
             for j in 0..h {
                 match map.get_tile(layer, i, j) {
-                    Some(v) => items.push(GameItem::new((i as usize, j as usize), v.id)),
+                    Some(v) => {
+                        let mut item = GameItem::new((i as usize, j as usize), v.id);
+                        item.properties = LevelInfo::tile_properties(map, &v.tileset, v.id);
+                        items.push(item);
+                    }
                     None => continue,
                 }
             }
@@ -58,46 +111,250 @@ impl LevelInfo {
         items
     }
 
-    fn parse_level(map: &Map) -> Self {
-        // Size of the map
-        let w = map.layers[LAYER_WALLS].width;
-        let h = map.layers[LAYER_WALLS].height;
+    /// Row major flags of which tiles are set in the layer
+    fn get_grid(w: u32, h: u32, layer: &str, map: &Map) -> Vec<bool> {
+        if !is_tile_layer(map, layer) {
+            return vec![false; (w * h) as usize];
+        }
+
+        (0..h)
+            .flat_map(|j| (0..w).map(move |i| (i, j)))
+            .map(|(i, j)| map.get_tile(layer, i, j).is_some())
+            .collect()
+    }
+
+    /// Objects of every object layer, by kind (the object's type, or its
+    /// name if it has none) as items on the tile they're placed on. Tile
+    /// objects get the properties of their tile, overridden by the object's
+    fn get_objects(map: &Map) -> Vec<(String, GameItem)> {
+        let raw = &map.raw_tiled_map;
+        let (tw, th) = (raw.tilewidth as f32, raw.tileheight as f32);
+        let mut objects = Vec::new();
+
+        for layer in raw.layers.iter().filter(|l| l.ty == "objectgroup") {
+            for object in layer.objects.iter() {
+                let kind = match object.ty.is_empty() {
+                    true => object.name.to_lowercase(),
+                    false => object.ty.to_lowercase(),
+                };
+
+                // Tile objects are anchored at their bottom left corner
+                let gid = object.gid.map(|gid| gid & GID_MASK);
+                let y = match gid {
+                    Some(_) => object.y - object.height,
+                    None => object.y,
+                };
+                let pos = (
+                    (object.x / tw).max(0.0) as usize,
+                    (y / th).max(0.0) as usize,
+                );
+
+                let mut item = GameItem::new(pos, gid.unwrap_or(0));
+                if let Some(gid) = gid {
+                    if let Some(tileset) = raw
+                        .tilesets
+                        .iter()
+                        .find(|t| gid >= t.firstgid && gid < t.firstgid + t.tilecount)
+                    {
+                        item.value = gid - tileset.firstgid;
+                        item.properties =
+                            LevelInfo::tile_properties(map, &tileset.name, item.value);
+                    }
+                }
+                for p in object.properties.iter() {
+                    item.properties.insert(p.name.clone(), p.value.clone());
+                }
+                if ["crab", "chaser", "turret", "timed_spike"].contains(&kind.as_str()) {
+                    item.properties.insert("enemy".to_string(), kind.clone());
+                }
 
-        // Search for a door
-        let door = LevelInfo::get_one_item(w, h, LAYER_DOOR, map);
-        if door.is_none() {
-            panic!("No doors found");
+                objects.push((kind, item));
+            }
         }
 
-        // Search for keys
-        let key = LevelInfo::get_one_item(w, h, LAYER_KEYS, map);
-        if key.is_none() {
-            panic!("No keys found");
+        objects
+    }
+
+    /// Properties set on a tile in the tileset (the `tiles` list of the map json)
+    fn tile_properties(map: &Map, tileset: &str, id: u32) -> HashMap<String, String> {
+        map.raw_tiled_map
+            .tilesets
+            .iter()
+            .filter(|t| t.name == tileset)
+            .flat_map(|t| t.tiles.iter())
+            .filter(|t| t.id as u32 == id)
+            .flat_map(|t| t.properties.iter())
+            .map(|p| (p.name.clone(), p.value.clone()))
+            .collect()
+    }
+
+    fn parse_level(map: &Map) -> Result<Self, String> {
+        // Tile lookups index the layer data by the layer's size
+        if let Some((name, _)) = map.layers.iter().find(|(_, layer)| {
+            !layer.data.is_empty() && layer.data.len() != (layer.width * layer.height) as usize
+        }) {
+            return Err(format!("Layer {} is missing tiles", name));
         }
 
+        // Size of the map
+        let w = map.raw_tiled_map.width;
+        let h = map.raw_tiled_map.height;
+        let objects = LevelInfo::get_objects(map);
+        let objects_of = |kinds: &[&str]| -> Vec<GameItem> {
+            objects
+                .iter()
+                .filter(|(kind, _)| kinds.contains(&kind.as_str()))
+                .map(|(_, item)| item.clone())
+                .collect()
+        };
+        let first_object = |kinds: &[&str]| objects_of(kinds).first().map(|o| o.pos);
+
+        // Search for a door, tile layers first then objects
+        let door = LevelInfo::get_one_item(w, h, LAYER_DOOR, map)
+            .or(first_object(&["door"]))
+            .ok_or("No doors found")?;
+
+        // Search for keys
+        let key = LevelInfo::get_one_item(w, h, LAYER_KEYS, map)
+            .or(first_object(&["key"]))
+            .ok_or("No keys found")?;
+
         // Search for player spawn pos
-        let player = LevelInfo::get_one_item(w, h, LAYER_PLAYER, map);
-        if player.is_none() {
-            panic!("No player spawn loc");
+        let player = LevelInfo::get_one_item(w, h, LAYER_PLAYER, map)
+            .or(first_object(&["player", "spawn"]))
+            .ok_or("No player spawn loc")?;
+
+        // Randomised placements, the `player` and `keys` layers can hold
+        // more than one tile too
+        let mut spawn_candidates =
+            LevelInfo::get_candidates(w, h, &[LAYER_PLAYER, LAYER_SPAWNS], map);
+        let mut key_candidates =
+            LevelInfo::get_candidates(w, h, &[LAYER_KEYS, LAYER_KEY_SPAWNS], map);
+        for (candidates, kinds) in [
+            (&mut spawn_candidates, &["player", "spawn"][..]),
+            (&mut key_candidates, &["key"][..]),
+        ] {
+            for item in objects_of(kinds) {
+                if !candidates.contains(&item.pos) {
+                    candidates.push(item.pos);
+                }
+            }
         }
 
         // Other details
-        let spikes = LevelInfo::get_all_items(w, h, LAYER_SPIKES, map);
-        let enemies = LevelInfo::get_all_items(w, h, LAYER_ENEMIES, map);
+        let mut spikes = LevelInfo::get_all_items(w, h, LAYER_SPIKES, map);
+        spikes.extend(objects_of(&["spike", "timed_spike"]));
+        let mut enemies = LevelInfo::get_all_items(w, h, LAYER_ENEMIES, map);
+        enemies.extend(objects_of(&["enemy", "crab", "chaser", "turret"]));
+        for item in enemies.iter() {
+            if let Some(path) = item.property("path") {
+                parse_path(path).map_err(|e| format!("Enemy at {:?}: {}", item.pos, e))?;
+            }
+        }
+        let walls = LevelInfo::get_grid(w, h, LAYER_WALLS, map);
+        let ice = LevelInfo::get_grid(w, h, LAYER_ICE, map);
 
-        Self {
+        Ok(Self {
             size: (w as usize, h as usize),
-            key: key.unwrap(),
-            door: door.unwrap(),
-            agent: player.unwrap(),
+            key,
+            door,
+            agent: player,
+            spawn_candidates,
+            key_candidates,
+            walls,
+            ice,
             spikes,
             enemies,
+        })
+    }
+}
+
+// Only the parts of the map json that `macroquad_tiled` skips
+#[derive(DeJson)]
+struct RawMap {
+    #[nserde(default)]
+    layers: Vec<RawLayer>,
+}
+
+#[derive(DeJson)]
+struct RawLayer {
+    #[nserde(default)]
+    objects: Vec<RawObject>,
+}
+
+#[derive(DeJson)]
+struct RawObject {
+    #[nserde(default)]
+    x: f32,
+    #[nserde(default)]
+    y: f32,
+    #[nserde(default)]
+    polyline: Vec<RawPoint>,
+}
+
+#[derive(DeJson)]
+struct RawPoint {
+    x: f32,
+    y: f32,
+}
+
+/// Loads a Tiled map, saved as json or tmx. Polylines of objects are turned
+/// into a `path` property of the tiles they pass through, "x,y;x,y;..".
+/// Fails on maps that can't be read, e.g. while Tiled is still writing them
+pub fn load_map(data: &str, textures: &[(&str, Texture2D)]) -> Result<Map, String> {
+    let json = match data.trim_start().starts_with('<') {
+        true => tmx::tmx_to_json(data)?,
+        false => data.to_string(),
+    };
+    let mut map =
+        macroquad_tiled::load_map(&json, textures, &[]).map_err(|e| format!("{:?}", e))?;
+
+    let raw: RawMap = DeJson::deserialize_json(&json).map_err(|e| e.to_string())?;
+    let (tw, th) = (
+        map.raw_tiled_map.tilewidth as f32,
+        map.raw_tiled_map.tileheight as f32,
+    );
+    for (layer, raw_layer) in map.raw_tiled_map.layers.iter_mut().zip(raw.layers) {
+        for (object, raw_object) in layer.objects.iter_mut().zip(raw_layer.objects) {
+            if raw_object.polyline.is_empty() {
+                continue;
+            }
+            let path: Vec<String> = raw_object
+                .polyline
+                .iter()
+                .map(|p| {
+                    let x = ((raw_object.x + p.x) / tw).max(0.0) as usize;
+                    let y = ((raw_object.y + p.y) / th).max(0.0) as usize;
+                    format!("{},{}", x, y)
+                })
+                .collect();
+            object.properties.retain(|p| p.name != "path");
+            object.properties.push(Property {
+                name: "path".to_string(),
+                ty: "string".to_string(),
+                value: path.join(";"),
+            });
         }
     }
+
+    Ok(map)
+}
+
+/// Object layers share the layer namespace, but have no tiles to look up
+pub fn is_tile_layer(map: &Map, layer: &str) -> bool {
+    map.contains_layer(layer) && !map.layers[layer].data.is_empty()
 }
 
 impl GameItem {
-    fn new(pos: (usize, usize), value: u32) -> Self {
-        Self { pos, value }
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties.get(name).map(|v| v.as_str())
+    }
+
+    pub fn new(pos: (usize, usize), value: u32) -> Self {
+        Self {
+            pos,
+            value,
+            properties: HashMap::new(),
+        }
     }
 }
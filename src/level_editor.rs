@@ -0,0 +1,280 @@
+use std::io;
+
+use macroquad::prelude::*;
+
+use crate::generator::{Crab, Room};
+use crate::*;
+
+/// What a left click paints onto the room
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EditTool {
+    Wall,
+    Floor,
+    Spike,
+    CrabHorizontal,
+    CrabVertical,
+    Key,
+    Door,
+    Spawn,
+}
+
+/// Paints a room tile by tile, with undo/redo and a live solvability check
+pub struct LevelEditor {
+    pub room: Room,
+    // Index of the level the room was opened from, see `RESOURCES`
+    pub level: usize,
+    pub tool: EditTool,
+    pub is_solvable: bool,
+    // Outcome of the last save, shown in the panel
+    pub last_save: Option<io::Result<()>>,
+    undo_stack: Vec<Room>,
+    redo_stack: Vec<Room>,
+    // Room at the start of the current mouse stroke
+    stroke_start: Option<Room>,
+}
+
+impl EditTool {
+    pub const ALL: [EditTool; 8] = [
+        EditTool::Wall,
+        EditTool::Floor,
+        EditTool::Spike,
+        EditTool::CrabHorizontal,
+        EditTool::CrabVertical,
+        EditTool::Key,
+        EditTool::Door,
+        EditTool::Spawn,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EditTool::Wall => "Wall",
+            EditTool::Floor => "Floor",
+            EditTool::Spike => "Spike",
+            EditTool::CrabHorizontal => "Crab -",
+            EditTool::CrabVertical => "Crab |",
+            EditTool::Key => "Key",
+            EditTool::Door => "Door",
+            EditTool::Spawn => "Spawn",
+        }
+    }
+}
+
+impl LevelEditor {
+    /// Starts from one of the loaded levels, fails if the level can't be
+    /// edited as a room, see `Room::from_level`
+    pub fn new(level: usize) -> Result<Self, String> {
+        let room = Room::from_level(&RESOURCES.level(level).unwrap().lvl_info)?;
+        Ok(Self {
+            level,
+            is_solvable: room.is_solvable(),
+            room,
+            tool: EditTool::Wall,
+            last_save: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            stroke_start: None,
+        })
+    }
+
+    /// `mouse_pos` is in world coordinates, `None` while the mouse is over the ui
+    pub fn update(&mut self, mouse_pos: Option<Vec2>) {
+        // Number keys pick a tool
+        let tool_keys = [
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Key4,
+            KeyCode::Key5,
+            KeyCode::Key6,
+            KeyCode::Key7,
+            KeyCode::Key8,
+        ];
+        for (key, tool) in tool_keys.iter().zip(EditTool::ALL) {
+            if is_key_pressed(*key) {
+                self.tool = tool;
+            }
+        }
+
+        let is_ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        let is_shift = is_key_down(KeyCode::LeftShift);
+        if is_ctrl && is_key_pressed(KeyCode::Z) {
+            match is_shift {
+                true => self.redo(),
+                false => self.undo(),
+            }
+        }
+        if is_ctrl && is_key_pressed(KeyCode::Y) {
+            self.redo();
+        }
+
+        // A mouse stroke is a single undo step
+        if is_mouse_button_down(MouseButton::Left) {
+            if let Some(pos) = mouse_pos.and_then(|p| self.tile_at(p)) {
+                if self.stroke_start.is_none() {
+                    self.stroke_start = Some(self.room.clone());
+                }
+                let before = self.room.clone();
+                self.paint(pos);
+                if before != self.room {
+                    self.is_solvable = self.room.is_solvable();
+                }
+            }
+        } else if let Some(start) = self.stroke_start.take() {
+            if start != self.room {
+                self.undo_stack.push(start);
+                self.redo_stack.clear();
+            }
+        }
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(room) = self.undo_stack.pop() {
+            self.redo_stack
+                .push(std::mem::replace(&mut self.room, room));
+            self.is_solvable = self.room.is_solvable();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(room) = self.redo_stack.pop() {
+            self.undo_stack
+                .push(std::mem::replace(&mut self.room, room));
+            self.is_solvable = self.room.is_solvable();
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    fn tile_at(&self, pos: Vec2) -> Option<(usize, usize)> {
+        let scale_factor = UNIT_FRAME_SIZE * FRAME_SCALE;
+        let (x, y) = (pos.x / scale_factor, pos.y / scale_factor);
+        if x < 0.0 || y < 0.0 || x >= self.room.size.0 as f32 || y >= self.room.size.1 as f32 {
+            return None;
+        }
+
+        Some((x as usize, y as usize))
+    }
+
+    fn paint(&mut self, pos: (usize, usize)) {
+        let room = &mut self.room;
+        let idx = pos.1 * room.size.0 + pos.0;
+
+        // The key, door and spawn are only ever moved, never painted over
+        let is_reserved = [room.agent, room.key, room.door].contains(&pos);
+        let clear = |room: &mut Room| {
+            room.spikes.retain(|s| *s != pos);
+            room.crabs.retain(|c| c.pos != pos);
+        };
+
+        match self.tool {
+            EditTool::Wall if !is_reserved => {
+                clear(room);
+                room.walls[idx] = true;
+            }
+            EditTool::Floor => {
+                clear(room);
+                room.walls[idx] = false;
+            }
+            EditTool::Spike if !is_reserved && !room.walls[idx] => {
+                clear(room);
+                room.spikes.push(pos);
+            }
+            EditTool::CrabHorizontal | EditTool::CrabVertical
+                if !is_reserved && !room.walls[idx] =>
+            {
+                clear(room);
+                room.crabs.push(Crab {
+                    pos,
+                    is_vertical: self.tool == EditTool::CrabVertical,
+                });
+            }
+            EditTool::Key if !room.walls[idx] && pos != room.door && pos != room.agent => {
+                clear(room);
+                room.key = pos;
+            }
+            EditTool::Spawn if !room.walls[idx] && pos != room.door && pos != room.key => {
+                clear(room);
+                room.agent = pos;
+            }
+            EditTool::Door if pos != room.key && pos != room.agent => {
+                clear(room);
+                room.walls[idx] = false;
+                room.door = pos;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn draw(&self) {
+        let scale_factor = UNIT_FRAME_SIZE * FRAME_SCALE;
+        let textures = TEXTURES.get().unwrap();
+        let room = &self.room;
+        let at = |(x, y): (usize, usize)| (x as f32 * scale_factor, y as f32 * scale_factor);
+        let params = || DrawTextureParams {
+            dest_size: Some(vec2(scale_factor, scale_factor)),
+            ..Default::default()
+        };
+
+        for y in 0..room.size.1 {
+            for x in 0..room.size.0 {
+                let color = match room.is_wall((x, y)) {
+                    true => Color::from_rgba(255, 236, 232, 255),
+                    false => Color::from_rgba(255, 190, 150, 255),
+                };
+                let (px, py) = at((x, y));
+                draw_rectangle(px, py, scale_factor, scale_factor, color);
+                draw_rectangle_lines(
+                    px,
+                    py,
+                    scale_factor,
+                    scale_factor,
+                    1.0,
+                    Color::new(0.0, 0.0, 0.0, 0.1),
+                );
+            }
+        }
+
+        for spike in room.spikes.iter() {
+            let (px, py) = at(*spike);
+            draw_texture_ex(textures.small_spike_texture, px, py, WHITE, params());
+        }
+        for crab in room.crabs.iter() {
+            let (px, py) = at(crab.pos);
+            let rotation = match crab.is_vertical {
+                true => 0.0,
+                false => std::f32::consts::FRAC_PI_2,
+            };
+            draw_texture_ex(
+                textures.crab_texture,
+                px,
+                py,
+                WHITE,
+                DrawTextureParams {
+                    rotation,
+                    ..params()
+                },
+            );
+        }
+
+        let (px, py) = at(room.door);
+        draw_rectangle(px, py, scale_factor, scale_factor, BROWN);
+        let (px, py) = at(room.key);
+        draw_texture_ex(textures.key_texture, px, py, WHITE, params());
+        let (px, py) = at(room.agent);
+        draw_texture_ex(textures.agent_texture, px, py, WHITE, params());
+
+        // Outline shows whether the room can be finished
+        let color = match self.is_solvable {
+            true => GREEN,
+            false => RED,
+        };
+        let (w, h) = at(room.size);
+        draw_rectangle_lines(0.0, 0.0, w, h, 4.0, color);
+    }
+}
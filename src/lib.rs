@@ -1,14 +1,27 @@
 pub mod agent;
+pub mod ascii;
 pub mod camera;
 pub mod configs;
+pub mod demo;
 pub mod editor;
 pub mod enemy;
+pub mod eval;
+pub mod export;
 pub mod ff;
 pub mod game;
+pub mod generator;
+pub mod heatmap;
+pub mod hot_reload;
+pub mod input;
 pub mod level;
+pub mod level_editor;
+pub mod minimap;
 pub mod population;
+pub mod recorder;
 pub mod resources;
+pub mod scheduler;
 pub mod simulation;
+pub mod tmx;
 
 pub use configs::*;
 pub use resources::RESOURCES;
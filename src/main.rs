@@ -1,9 +1,11 @@
-use escape::simulation::SimulationStats;
+use escape::scheduler::Scheduler;
+use escape::simulation::{Halt, SimulationStats};
 use macroquad::prelude::*;
-use std::thread::sleep;
 use std::time::Duration;
 
+use escape::agent::ActionSpace;
 use escape::editor::Editor;
+use escape::hot_reload::LevelWatcher;
 use escape::resources::init_resources;
 use escape::*;
 
@@ -17,45 +19,214 @@ fn window_conf() -> Conf {
     }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    // Headless modes, these don't open a window
+    if args.get(1).map(|a| a.as_str()) == Some("record") {
+        let out_dir = args.get(2).map(|a| a.as_str()).unwrap_or("recording");
+        let num_generations = args.get(3).and_then(|a| a.parse().ok()).unwrap_or(50);
+        let is_grid = args.iter().any(|a| a == "--grid");
+        recorder::record(out_dir, num_generations, is_grid);
+        return;
+    }
+    if args.get(1).map(|a| a.as_str()) == Some("play") {
+        let genome_path = args.get(2).map(|a| a.as_str());
+        let delay_ms = args.get(3).and_then(|a| a.parse().ok()).unwrap_or(100);
+        ascii::play(genome_path, delay_ms);
+        return;
+    }
+    if args.get(1).map(|a| a.as_str()) == Some("eval") {
+        let genome_path = args.get(2).expect("Usage: escape eval <genome>");
+        eval::run(genome_path);
+        return;
+    }
+
+    if args.get(1).map(|a| a.as_str()) == Some("generate") {
+        let out_path = args.get(2).map(|a| a.as_str()).unwrap_or("tiled/generated");
+        let value_of = |name: &str| {
+            args.iter()
+                .position(|a| a == name)
+                .and_then(|i| args.get(i + 1))
+        };
+        let mut config = generator::GeneratorConfig::default();
+        if let Some(seed) = value_of("--seed").and_then(|v| v.parse().ok()) {
+            config.seed = seed;
+        }
+        if let Some(difficulty) = value_of("--difficulty").and_then(|v| v.parse().ok()) {
+            config.difficulty = difficulty;
+        }
+        if let Some(size) = value_of("--size") {
+            let parsed = size
+                .split_once('x')
+                .and_then(|(w, h)| w.parse().ok().zip(h.parse().ok()));
+            match parsed {
+                Some(size) => config.size = size,
+                None => eprintln!("Invalid size {}, use <width>x<height>", size),
+            }
+        }
+        if args.iter().any(|a| a == "--maze") {
+            config.algorithm = generator::Algorithm::Maze;
+        }
+        generator::run(out_path, &config);
+        return;
+    }
+
+    macroquad::Window::from_config(window_conf(), run());
+}
+
+async fn run() {
     init_resources().await;
 
     let mut editor = Editor::new();
-    let mut simulation = Simulation::new();
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(name) = args
+        .iter()
+        .position(|a| a == "--actions")
+        .and_then(|i| args.get(i + 1))
+    {
+        match ActionSpace::from_name(name) {
+            Some(action_space) => editor.settings.action_space = action_space,
+            None => eprintln!(
+                "Unknown action space {}, use four, four_stay or eight",
+                name
+            ),
+        }
+    }
+    let mut simulation = Simulation::with_action_space(editor.settings.action_space);
     let mut stats = SimulationStats::new();
+    let mut level_watcher = LevelWatcher::new();
+    let mut scheduler = Scheduler::new(Duration::from_secs_f32(SIMULATION_TIME_BUDGET));
 
     loop {
         let (r, g, b, a) = WINDOW_BACKGROUND_COLOR;
         clear_background(Color::from_rgba(r, g, b, a));
 
-        if editor.settings.is_frame_skip {
-            for _ in 0..10 {
-                stats = simulation.update(&editor).unwrap_or(stats);
+        // The simulation is paused while a room is being edited
+        let is_running = !editor.settings.is_edit_mode;
+        let generation = stats.generation_count;
+        if is_running {
+            simulation.handle_user_input(&editor);
+            scheduler.run(
+                editor.settings.target_steps_per_second(),
+                || match simulation.update(&editor) {
+                    Some(new_stats) => {
+                        stats = new_stats;
+                        true
+                    }
+                    None => false,
+                },
+            );
+            stats.steps_per_second = scheduler.measured_rate();
+            // Breakpoints keep the step target, reaching it clears it
+            if let Some(halt) = simulation.take_halt() {
+                editor.settings.is_pause = true;
+                editor.settings.last_halt = Some(halt);
+                if matches!(halt, Halt::Reached(_)) {
+                    editor.settings.step_target = None;
+                }
             }
+            simulation.draw(&editor);
+        }
+        // The inspected game and its pin are gone with its generation, a
+        // replay of it keeps playing
+        if stats.generation_count != generation {
+            editor.settings.inspected_game = None;
         }
 
-        stats = simulation.update(&editor).unwrap_or(stats);
-        simulation.draw(&editor);
-
+        editor.set_follow_target(simulation.focus_pos(&editor));
         editor.update();
-        editor.draw(&stats);
+        let inspection = editor
+            .settings
+            .inspected_game
+            .and_then(|idx| simulation.inspect(idx));
+        editor.draw(&stats, inspection, simulation.demo());
 
-        if editor.settings.slow_mode {
-            sleep(Duration::from_millis(200));
-        }
         if editor.settings.is_restart {
             editor.settings.is_restart = false;
-            simulation = Simulation::new();
+            simulation = Simulation::with_action_space(editor.settings.action_space);
         }
-        if is_key_pressed(KeyCode::Escape) {
-            break;
+        // Clicking a game of the multi view zooms in on it and inspects it
+        if let Some(pos) = editor
+            .take_click()
+            .filter(|_| editor.settings.is_show_multiple)
+        {
+            editor.settings.inspected_game = simulation.game_at(pos);
+            if let Some(idx) = editor.settings.inspected_game {
+                editor.settings.focus_game = idx;
+                editor.settings.is_focus_game = true;
+            }
         }
-
-        if !editor.settings.is_draw {
-            for _ in 0..100 {
-                stats = simulation.update(&editor).unwrap_or(stats);
+        if let Some(idx) = editor.settings.inspected_game {
+            if editor.settings.is_replay_game {
+                simulation.replay(idx);
+                editor.settings.is_show_multiple = false;
+            }
+            if editor.settings.is_pin_game {
+                simulation.toggle_pin(idx);
+            }
+        }
+        if editor.settings.is_stop_replay {
+            simulation.stop_replay();
+        }
+        editor.settings.is_replay_game = false;
+        editor.settings.is_pin_game = false;
+        editor.settings.is_stop_replay = false;
+        // Play mode, demos are saved and queued for the next generation
+        if editor.settings.is_play_mode != simulation.demo().is_some() {
+            match editor.settings.is_play_mode {
+                true => {
+                    simulation.start_demo(&editor);
+                    editor.settings.is_show_multiple = false;
+                }
+                false => simulation.stop_demo(),
+            }
+        }
+        if editor.settings.is_use_demo {
+            if let Some(demo) = simulation.demo().filter(|d| d.frame_count() > 0) {
+                editor.last_demo_save = Some(demo.save(DEMO_DIR));
             }
+            simulation.use_demo();
+            stats.num_queued_demos = simulation.num_queued_demos();
+        }
+        if editor.settings.is_retry_demo {
+            simulation.retry_demo();
+        }
+        editor.settings.is_use_demo = false;
+        editor.settings.is_retry_demo = false;
+        if editor.settings.is_focus_game {
+            editor.settings.is_focus_game = false;
+            editor.settings.is_show_multiple = true;
+            if let Some(rect) = simulation.game_rect(editor.settings.focus_game) {
+                editor.focus(rect);
+            }
+        }
+        if editor.settings.is_train_on_room {
+            editor.settings.is_train_on_room = false;
+            editor.settings.is_edit_mode = false;
+            if let Some(level_editor) = &editor.level_editor {
+                resources::load_room(&level_editor.room, level_editor.level);
+            }
+            simulation = Simulation::with_action_space(editor.settings.action_space);
+        }
+        if IS_HOT_RELOAD && level_watcher.poll() {
+            match resources::reload_levels() {
+                Ok(()) => {
+                    println!("Reloaded the levels");
+                    level_watcher.loaded();
+                    match editor.settings.is_keep_genomes_on_reload {
+                        true => simulation.reload_level(),
+                        false => {
+                            simulation = Simulation::with_action_space(editor.settings.action_space)
+                        }
+                    }
+                }
+                Err(e) => eprintln!("{}, keeping the old levels", e),
+            }
+        }
+        if editor.settings.is_quit {
+            editor.save_camera();
+            break;
         }
 
         next_frame().await
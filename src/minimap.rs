@@ -0,0 +1,76 @@
+use macroquad::prelude::*;
+
+use crate::game::Game;
+use crate::*;
+
+/// Overview of a room that doesn't fit on screen, drawn in the top right
+/// corner with the area in view outlined. `games[0]` is the selected game,
+/// the rest show up as dots
+pub fn draw(games: &[Game], visible: Rect, camera: &Camera2D) {
+    let Some(game) = games.first() else {
+        return;
+    };
+    let (w, h) = game.draw_size();
+    let is_room_visible =
+        visible.x <= 0.0 && visible.y <= 0.0 && visible.right() >= w && visible.bottom() >= h;
+    if is_room_visible {
+        return;
+    }
+
+    let scale = MINIMAP_SIZE / w.max(h);
+    let tile = UNIT_FRAME_SIZE * FRAME_SCALE * scale;
+    let origin = vec2(screen_width() - w * scale - 20.0, 20.0);
+    let at = |(x, y): (usize, usize)| origin + vec2(x as f32 + 0.5, y as f32 + 0.5) * tile;
+
+    // Screen space, the world camera is restored at the end
+    set_default_camera();
+    draw_rectangle(
+        origin.x - 4.0,
+        origin.y - 4.0,
+        w * scale + 8.0,
+        h * scale + 8.0,
+        Color::new(0.0, 0.0, 0.0, 0.6),
+    );
+    draw_texture_ex(
+        RESOURCES.level(game.level).unwrap().lvl_background_sprite,
+        origin.x,
+        origin.y,
+        WHITE,
+        DrawTextureParams {
+            dest_size: Some(vec2(w, h) * scale),
+            ..Default::default()
+        },
+    );
+
+    let door = at(game.lvl.door);
+    draw_rectangle(door.x - tile / 2.0, door.y - tile / 2.0, tile, tile, BROWN);
+    if !game.is_key_collected {
+        let key = at(game.lvl.key);
+        draw_circle(key.x, key.y, tile.max(4.0) / 2.0, GOLD);
+    }
+
+    for g in games.iter().skip(1).filter(|g| !g.is_dead) {
+        let pos = at(*g.get_current_agent_pos());
+        draw_circle(pos.x, pos.y, 1.5, Color::new(1.0, 1.0, 1.0, 0.3));
+    }
+    if !game.is_dead {
+        let pos = at(*game.get_current_agent_pos());
+        draw_circle(pos.x, pos.y, tile.max(5.0) / 2.0, SKYBLUE);
+    }
+
+    // Part of the room on screen
+    let view_min = origin + vec2(visible.x.max(0.0), visible.y.max(0.0)) * scale;
+    let view_max = origin + vec2(visible.right().min(w), visible.bottom().min(h)) * scale;
+    if view_max.x > view_min.x && view_max.y > view_min.y {
+        draw_rectangle_lines(
+            view_min.x,
+            view_min.y,
+            view_max.x - view_min.x,
+            view_max.y - view_min.y,
+            2.0,
+            WHITE,
+        );
+    }
+
+    set_camera(camera);
+}
@@ -3,53 +3,118 @@ use std::collections::HashMap;
 use ::rand::distributions::WeightedIndex;
 use ::rand::prelude::Distribution;
 use ::rand::thread_rng;
+use macroquad::color::hsl_to_rgb;
 use macroquad::prelude::*;
 
-use crate::agent::AgentCommand;
-use crate::editor::Editor;
+use crate::agent::ActionSpace;
+use crate::editor::{Editor, FlowFieldView};
 use crate::ff::FF;
-use crate::game::Game;
+use crate::game::{FitnessBreakdown, Game};
+use crate::heatmap::Heatmap;
 use crate::*;
 
 struct FFInfo {
-    pub key: HashMap<(usize, usize), usize>,
+    // One field per key candidate, keys can move between episodes
+    pub keys: HashMap<(usize, usize), HashMap<(usize, usize), usize>>,
     pub door: HashMap<(usize, usize), usize>,
+    // Farther than any tile, for the ones the fields don't reach
+    max_distance: usize,
 }
 
 pub struct Population {
+    // Index of the level in `RESOURCES`
+    level: usize,
+    action_space: ActionSpace,
     ff_info: FFInfo,
     games: Vec<Game>,
+    // Where agents died in the last completed generation
+    death_map: Heatmap,
+    // Agent frames spent on each tile, this generation and since start
+    visit_map: Heatmap,
+    total_visit_map: Heatmap,
+    // Games picked in the inspector to parent the next generation
+    pinned: Vec<usize>,
+    // Genomes played by hand, bred into the next generation
+    demos: Vec<Vec<u8>>,
+    // Fitness of the best game of the last completed generation
+    best_fitness: f32,
+}
+
+impl Default for Population {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Population {
     pub fn new() -> Self {
+        Population::on_level(0, NUM_GAMES as usize)
+    }
+
+    pub fn on_level(level: usize, num_games: usize) -> Self {
+        Population::with_action_space(level, num_games, ACTION_SPACE)
+    }
+
+    pub fn with_action_space(level: usize, num_games: usize, action_space: ActionSpace) -> Self {
+        let resources = RESOURCES.level(level).unwrap();
         Self {
-            ff_info: FFInfo::new(),
-            games: (0..NUM_GAMES).map(|_| Game::new()).collect(),
+            level,
+            action_space,
+            ff_info: FFInfo::new(level),
+            games: (0..num_games)
+                .map(|_| Game::with_action_space(level, action_space))
+                .collect(),
+            death_map: Heatmap::new(resources.lvl_info.size),
+            visit_map: Heatmap::new(resources.lvl_info.size),
+            total_visit_map: Heatmap::new(resources.lvl_info.size),
+            pinned: Vec::new(),
+            demos: Vec::new(),
+            best_fitness: 0.0,
         }
     }
 
-    pub fn update(&mut self, frame_count: usize, editor: &Editor) {
-        // User input applies only to 1st game
-        self.handle_user_input();
+    /// Carries the genomes over to a reloaded level, the flow fields and
+    /// heatmaps are rebuilt for it
+    pub fn reload_level(&mut self) {
+        let games = self.games.iter().map(Game::clone_with_moves).collect();
+        *self = Population::with_action_space(self.level, self.games.len(), self.action_space);
+        self.games = games;
+    }
 
+    pub fn update(&mut self, frame_count: usize, editor: &Editor) {
         if !editor.settings.is_ai_enabled {
             return;
         }
         self.games.iter_mut().for_each(|g| g.update(frame_count));
+        self.update_visit_maps();
     }
 
     pub fn selection(&mut self) {
         let mut rng = thread_rng();
         let gene_pool = self.calc_fitness();
+        self.update_death_map();
+        self.visit_map.clear();
         let mut new_games = Vec::new();
 
-        let num_retained = NUM_GAMES as f32 * (POP_RETENTION_RATE / 100.0);
-        let num_expo = NUM_GAMES as f32 * (POP_EXPO_PERCENTAGE / 100.0);
-        let num_children = NUM_GAMES as f32 - num_retained - num_expo;
-        
-        for _ in 0..num_children as usize {
-            let first = self.games[gene_pool.sample(&mut rng)].clone();
+        // Children make up the rest, so the population keeps its size when
+        // the percentages don't divide it evenly
+        let num_games = self.games.len();
+        let num_retained = (num_games as f32 * (POP_RETENTION_RATE / 100.0)) as usize;
+        let num_expo = (num_games as f32 * (POP_EXPO_PERCENTAGE / 100.0)) as usize;
+        let num_children = num_games - num_retained - num_expo;
+        new_games.append(&mut self.demo_children(num_children));
+        let num_children = num_children - new_games.len();
+
+        // Pinned games are the first parent of a share of the children
+        let num_pinned_children = match self.pinned.is_empty() {
+            true => 0,
+            false => (num_children as f32 * (PINNED_PARENT_PERCENTAGE / 100.0)) as usize,
+        };
+        for i in 0..num_children {
+            let first = match i < num_pinned_children {
+                true => self.games[self.pinned[i % self.pinned.len()]].clone(),
+                false => self.games[gene_pool.sample(&mut rng)].clone(),
+            };
             let second = self.games[gene_pool.sample(&mut rng)].clone();
             let new_game = Game::crossover(&first, &second);
             new_games.push(new_game);
@@ -58,30 +123,70 @@ impl Population {
         // Retain the best games from the current gen
         self.games
             .sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap());
-        let retained_agents: Vec<Game> = (0..num_retained as usize)
+        self.best_fitness = self.games[0].fitness;
+        let retained_agents: Vec<Game> = (0..num_retained)
             .map(|i| Game::clone_with_moves(&self.games[i]))
             .collect();
 
         // Exploration agents
-        let mut exploration_agents: Vec<Game> = (0..num_expo as usize)
-            .map(|_| Game::new())
+        let mut exploration_agents: Vec<Game> = (0..num_expo)
+            .map(|_| Game::with_action_space(self.level, self.action_space))
             .collect();
 
+        self.pinned.clear();
         self.games.clear();
         self.games = retained_agents;
         self.games.append(&mut exploration_agents);
         self.games.append(&mut new_games);
     }
 
+    /// Children of the queued demos, each demo once as is and mutations of
+    /// it for the rest of its share. The queue is emptied
+    fn demo_children(&mut self, num_children: usize) -> Vec<Game> {
+        if self.demos.is_empty() {
+            return Vec::new();
+        }
+
+        let share = (num_children as f32 * (DEMO_CHILDREN_PERCENTAGE / 100.0)) as usize;
+        let num_demo_children = share.max(self.demos.len()).min(num_children);
+        let demos: Vec<Game> = self
+            .demos
+            .drain(..)
+            .map(|moves| {
+                let mut game = Game::on_level_with_moves(self.level, &moves);
+                game.action_space = self.action_space;
+                game
+            })
+            .collect();
+
+        (0..num_demo_children)
+            .map(|i| {
+                let demo = &demos[i % demos.len()];
+                match i < demos.len() {
+                    true => Game::clone_with_moves(demo),
+                    false => Game::mutate(demo),
+                }
+            })
+            .collect()
+    }
+
     fn calc_fitness(&mut self) -> WeightedIndex<f32> {
         let mut max_fitness = 0.0;
         let mut weights = Vec::new();
 
         for g in self.games.iter_mut() {
-            let agent_pos = g.get_current_agent_pos();
-            let ff_key = self.ff_info.key.get(&agent_pos).unwrap();
-            let ff_door = self.ff_info.door.get(&agent_pos).unwrap();
-            let fitness = g.fitness(ff_key, ff_door);
+            let (ff_key, ff_door) = self.ff_info.distances(g);
+            let mut fitness = g.fitness(&ff_key, &ff_door);
+
+            // Average over extra episodes of the same moves
+            let num_extra_rollouts = NUM_ROLLOUTS.saturating_sub(1);
+            for mut rollout in std::iter::repeat_with(|| g.rollout()).take(num_extra_rollouts) {
+                let (ff_key, ff_door) = self.ff_info.distances(&rollout);
+                fitness += rollout.fitness(&ff_key, &ff_door);
+            }
+            fitness /= (num_extra_rollouts + 1) as f32;
+            g.fitness = fitness;
+
             if fitness > max_fitness {
                 max_fitness = fitness;
             }
@@ -94,52 +199,211 @@ impl Population {
         WeightedIndex::new(&weights).expect("Failed to generate gene pool")
     }
 
-    fn handle_user_input(&mut self) {
-        if is_key_pressed(KeyCode::W) {
-            self.games[0].update_manual(AgentCommand::Top);
-        } else if is_key_pressed(KeyCode::A) {
-            self.games[0].update_manual(AgentCommand::Left);
-        } else if is_key_pressed(KeyCode::S) {
-            self.games[0].update_manual(AgentCommand::Bottom);
-        } else if is_key_pressed(KeyCode::D) {
-            self.games[0].update_manual(AgentCommand::Right);
+    fn update_death_map(&mut self) {
+        self.death_map.clear();
+        self.games
+            .iter()
+            .filter_map(|g| g.death.as_ref())
+            .for_each(|d| self.death_map.add(d.pos));
+    }
+
+    fn update_visit_maps(&mut self) {
+        for g in self.games.iter().filter(|g| !g.is_dead && !g.is_complete) {
+            let pos = *g.get_current_agent_pos();
+            self.visit_map.add(pos);
+            self.total_visit_map.add(pos);
+        }
+    }
+
+    pub fn games(&self) -> &[Game] {
+        &self.games
+    }
+
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn action_space(&self) -> ActionSpace {
+        self.action_space
+    }
+
+    /// Fitness of a game if its episode ended now
+    pub fn fitness_breakdown(&self, idx: usize) -> FitnessBreakdown {
+        let g = &self.games[idx];
+        let (ff_key, ff_door) = self.ff_info.distances(g);
+
+        g.fitness_breakdown(&ff_key, &ff_door)
+    }
+
+    pub fn is_pinned(&self, idx: usize) -> bool {
+        self.pinned.contains(&idx)
+    }
+
+    /// Pins the game as a parent for the next generation, or unpins it
+    pub fn toggle_pin(&mut self, idx: usize) {
+        match self.is_pinned(idx) {
+            true => self.pinned.retain(|i| *i != idx),
+            false => self.pinned.push(idx),
+        }
+    }
+
+    /// Queues a genome played by hand for the next generation
+    pub fn add_demo(&mut self, moves: Vec<u8>) {
+        self.demos.push(moves);
+    }
+
+    pub fn num_queued_demos(&self) -> usize {
+        self.demos.len()
+    }
+
+    pub fn best_fitness(&self) -> f32 {
+        self.best_fitness
+    }
+
+    pub fn num_deaths(&self) -> u32 {
+        self.death_map.total()
+    }
+
+    pub fn draw_single(&self, editor: &Editor) {
+        let scale_factor = UNIT_FRAME_SIZE * FRAME_SCALE;
+        self.games[0].draw(0.0, 0.0);
+
+        if editor.settings.is_show_visit_map {
+            let visit_map = match editor.settings.is_cumulative_visit_map {
+                true => &self.total_visit_map,
+                false => &self.visit_map,
+            };
+            visit_map.draw_ramp(scale_factor, 0.0, 0.0);
+        }
+        let ff = match editor.settings.flow_field_view {
+            FlowFieldView::Hidden => None,
+            FlowFieldView::Key => self.ff_info.keys.get(&self.games[0].lvl.key),
+            FlowFieldView::Door => Some(&self.ff_info.door),
+        };
+        if let Some(ff) = ff {
+            FF::draw(
+                ff,
+                editor.settings.is_show_ff_values,
+                scale_factor,
+                0.0,
+                0.0,
+            );
+        }
+        if editor.settings.is_show_death_map {
+            self.death_map.draw(RED, scale_factor, 0.0, 0.0);
+        }
+
+        if editor.settings.is_show_all_dots {
+            self.games
+                .iter()
+                .filter(|g| !g.is_dead)
+                .for_each(|g| g.draw_dot(Color::new(1.0, 1.0, 1.0, 0.4), 0.0, 0.0));
+        }
+
+        // Retained games are placed first and sorted by their last fitness,
+        // so the first few games are the top agents of the previous generation
+        let num_ghosts = editor.settings.num_ghosts.min(self.games.len());
+        for (i, g) in self.games[..num_ghosts].iter().enumerate() {
+            let hue = i as f32 / num_ghosts as f32;
+            g.draw_ghost(hsl_to_rgb(hue, 0.8, 0.6), 0.0, 0.0);
         }
     }
 
-    pub fn draw(&self, editor: &Editor) {
-        if !editor.settings.is_show_multiple {
-            self.games[0].draw(0.0, 0.0);
+    /// Draws the games in a grid, below the blocks of the levels before this one
+    pub fn draw_multi(&self, editor: &Editor) {
+        let visible = editor.visible_rect();
+        let games: Vec<(&Game, Rect)> = self
+            .games
+            .iter()
+            .enumerate()
+            .map(|(i, g)| (g, self.game_rect(i)))
+            .filter(|(_, r)| r.overlaps(&visible))
+            .collect();
+        if games.is_empty() {
             return;
         }
 
-        // Draw all games
-        let mut offset_x = 0.0;
-        let mut offset_y = 0.0;
-        let grid_padding = 40.0;
-        let (w, h) = self.games[0].lvl.size;
-        let (w, h) = (w as f32, h as f32);
-        let (w, h) = (
-            w * UNIT_FRAME_SIZE * FRAME_SCALE,
-            h * UNIT_FRAME_SIZE * FRAME_SCALE,
-        );
-        for g in self.games.iter() {
-            g.draw(offset_x, offset_y);
-
-            offset_x += w + grid_padding;
-            if offset_x >= w * NUM_GAMES_IN_ROW as f32 {
-                offset_y += h + grid_padding;
-                offset_x = 0.0;
-            }
+        let room_px = games[0].1.w / visible.w * screen_width();
+        if room_px < MULTI_VIEW_LOD_WIDTH {
+            games.iter().for_each(|(g, r)| g.draw_low_detail(r.x, r.y));
+            return;
         }
+
+        // Layer by layer, so consecutive draws share a texture and get batched
+        games.iter().for_each(|(g, r)| g.draw_background(r.x, r.y));
+        games.iter().for_each(|(g, r)| g.draw_door(r.x, r.y));
+        games.iter().for_each(|(g, r)| g.draw_key(r.x, r.y));
+        games.iter().for_each(|(g, r)| g.draw_agent(r.x, r.y));
+        games.iter().for_each(|(g, r)| g.draw_enemies(r.x, r.y));
     }
+
+    /// World space area of a game in the multi view grid
+    pub fn game_rect(&self, idx: usize) -> Rect {
+        let (w, h) = self.games[idx].draw_size();
+        let num_columns = num_columns(self.level);
+        let col = idx % num_columns;
+        let row = idx / num_columns;
+        let top: f32 = (0..self.level)
+            .map(|l| block_height(l, self.games.len()) + MULTI_VIEW_PADDING * 2.0)
+            .sum();
+
+        Rect::new(
+            col as f32 * (w + MULTI_VIEW_PADDING),
+            top + row as f32 * (h + MULTI_VIEW_PADDING),
+            w,
+            h,
+        )
+    }
+}
+
+/// Games per row in the multi view. Levels wider than the main one get fewer
+/// columns, so the blocks of all levels are about as wide
+fn num_columns(level: usize) -> usize {
+    let width = |l| RESOURCES.level(l).unwrap().lvl_info.size.0;
+    (NUM_GAMES_IN_ROW as usize * width(0) / width(level)).max(1)
+}
+
+/// Height of the block of a level's games in the multi view
+fn block_height(level: usize, num_games: usize) -> f32 {
+    let scale_factor = UNIT_FRAME_SIZE * FRAME_SCALE;
+    let h = RESOURCES.level(level).unwrap().lvl_info.size.1 as f32 * scale_factor;
+    let num_rows = num_games.div_ceil(num_columns(level));
+
+    num_rows as f32 * (h + MULTI_VIEW_PADDING)
 }
 
 impl FFInfo {
-    pub fn new() -> Self {
-        let resources = RESOURCES.get().unwrap();
+    pub fn new(level: usize) -> Self {
+        let resources = RESOURCES.level(level).unwrap();
+        let lvl = &resources.lvl_info;
+        let solve = |start| FF::with_walls(start, &lvl.size, lvl.walls.clone()).solve();
+        let keys = lvl
+            .key_candidates
+            .iter()
+            .chain([&lvl.key])
+            .map(|k| (*k, solve(k)))
+            .collect();
         Self {
-            key: FF::new(&resources.lvl_info.key, &resources.lvl_info.size).solve(),
-            door: FF::new(&resources.lvl_info.door, &resources.lvl_info.size).solve(),
+            keys,
+            door: solve(&lvl.door),
+            max_distance: lvl.size.0 * lvl.size.1,
         }
     }
+
+    /// Distances of the agent of the game to its key and to the door. An
+    /// agent off the fields, like one that slid somewhere the fields don't
+    /// reach, is as far as can be
+    pub fn distances(&self, game: &Game) -> (usize, usize) {
+        let agent_pos = game.get_current_agent_pos();
+        let distance = |field: Option<&HashMap<(usize, usize), usize>>| {
+            field
+                .and_then(|f| f.get(agent_pos).copied())
+                .unwrap_or(self.max_distance)
+        };
+
+        (
+            distance(self.keys.get(&game.lvl.key)),
+            distance(Some(&self.door)),
+        )
+    }
 }
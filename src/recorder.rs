@@ -0,0 +1,342 @@
+use std::fs;
+use std::io;
+
+use macroquad::prelude::*;
+
+use crate::editor::Editor;
+use crate::enemy::SpriteKind;
+use crate::game::Game;
+use crate::level::is_tile_layer;
+use crate::resources::init_resources_headless;
+use crate::*;
+
+/// Software rasteriser for rooms, doesn't need a window or a GPU
+pub struct Recorder {
+    scale: u32,
+    lvl_background: Image,
+    tileset: Image,
+    tileset_columns: u32,
+    agent: Image,
+    key: Image,
+    crab: Image,
+    small_spike: Image,
+    large_spike: Image,
+}
+
+impl Recorder {
+    pub fn new(scale: u32) -> Self {
+        let resources = RESOURCES.get().unwrap();
+        let tileset_columns = resources
+            .lvl_map
+            .tilesets
+            .values()
+            .next()
+            .map(|t| t.columns)
+            .unwrap_or(1);
+
+        Self {
+            scale,
+            lvl_background: Recorder::load_image(LVL_BACKGROUND_SPRITE),
+            tileset: Recorder::load_image(TILESET_PATH),
+            tileset_columns,
+            agent: Recorder::load_image("assets/agent.png"),
+            key: Recorder::load_image("assets/key.png"),
+            crab: Recorder::load_image("assets/crab.png"),
+            small_spike: Recorder::load_image("assets/small_spike.png"),
+            large_spike: Recorder::load_image("assets/large_spike.png"),
+        }
+    }
+
+    fn load_image(path: &str) -> Image {
+        let bytes = fs::read(path).unwrap_or_else(|_| panic!("Failed to read {}", path));
+        Image::from_file_with_format(&bytes, Some(ImageFormat::Png))
+    }
+
+    pub fn render_game(&self, game: &Game) -> Image {
+        let (w, h) = self.room_size(game);
+        let mut frame = Image::gen_image_color(w as u16, h as u16, BLACK);
+        self.draw_game(&mut frame, game, 0, 0);
+
+        frame
+    }
+
+    /// First few games laid out in a square grid, like the Show Multi view
+    pub fn render_grid(&self, games: &[Game]) -> Image {
+        let padding = 2 * self.scale;
+        let (w, h) = self.room_size(&games[0]);
+        let num_games = games.len().min(RECORD_GRID_SIZE * RECORD_GRID_SIZE);
+        let cols = RECORD_GRID_SIZE.min(num_games);
+        let rows = num_games.div_ceil(cols);
+        let mut frame = Image::gen_image_color(
+            (cols as u32 * (w + padding)) as u16,
+            (rows as u32 * (h + padding)) as u16,
+            BLACK,
+        );
+
+        for (i, g) in games.iter().take(num_games).enumerate() {
+            let x = (i % cols) as u32 * (w + padding);
+            let y = (i / cols) as u32 * (h + padding);
+            self.draw_game(&mut frame, g, x, y);
+        }
+
+        frame
+    }
+
+    fn room_size(&self, game: &Game) -> (u32, u32) {
+        let tile = UNIT_FRAME_SIZE as u32 * self.scale;
+        (game.lvl.size.0 as u32 * tile, game.lvl.size.1 as u32 * tile)
+    }
+
+    fn draw_game(&self, frame: &mut Image, game: &Game, x: u32, y: u32) {
+        let resources = RESOURCES.get().unwrap();
+        let tile = (UNIT_FRAME_SIZE as u32 * self.scale) as f32;
+        let (w, h) = self.room_size(game);
+        let (x, y) = (x as f32, y as f32);
+        let at = |(tx, ty): (usize, usize)| (x + tx as f32 * tile, y + ty as f32 * tile);
+
+        let background_tint = match game.is_complete {
+            true => Color::from_rgba(116, 242, 145, 255),
+            false => WHITE,
+        };
+        let full = Rect::new(0.0, 0.0, 1.0, 1.0);
+        let dest = Rect::new(x, y, w as f32, h as f32);
+        blit(
+            frame,
+            &self.lvl_background,
+            full,
+            dest,
+            0.0,
+            background_tint,
+        );
+
+        // Ice isn't part of the background image
+        if is_tile_layer(&resources.lvl_map, LAYER_ICE) {
+            for tx in 0..game.lvl.size.0 {
+                for ty in 0..game.lvl.size.1 {
+                    let ice = resources.lvl_map.get_tile(LAYER_ICE, tx as u32, ty as u32);
+                    if let Some(ice) = ice {
+                        let (px, py) = at((tx, ty));
+                        let dest = Rect::new(px, py, tile, tile);
+                        blit(
+                            frame,
+                            &self.tileset,
+                            self.tile_rect(ice.id),
+                            dest,
+                            0.0,
+                            WHITE,
+                        );
+                    }
+                }
+            }
+        }
+
+        if !game.is_key_collected {
+            let (dx, dy) = game.lvl.door;
+            let door = match is_tile_layer(&resources.lvl_map, LAYER_DOOR) {
+                true => resources.lvl_map.get_tile(LAYER_DOOR, dx as u32, dy as u32),
+                false => &None,
+            };
+            let (px, py) = at(game.lvl.door);
+            let dest = Rect::new(px, py, tile, tile);
+            match door {
+                Some(door) => blit(
+                    frame,
+                    &self.tileset,
+                    self.tile_rect(door.id),
+                    dest,
+                    0.0,
+                    WHITE,
+                ),
+                // Door placed as an object, there's no tile to draw
+                None => fill_rect(frame, dest, BROWN),
+            }
+
+            let (px, py) = at(game.lvl.key);
+            blit(
+                frame,
+                &self.key,
+                full,
+                Rect::new(px, py, tile, tile),
+                0.0,
+                WHITE,
+            );
+        }
+
+        if !game.is_dead {
+            let (px, py) = at(*game.get_current_agent_pos());
+            blit(
+                frame,
+                &self.agent,
+                full,
+                Rect::new(px, py, tile, tile),
+                0.0,
+                WHITE,
+            );
+        }
+
+        for sprite in game.enemy_sprites() {
+            let (px, py) = at(sprite.pos);
+            let image = match sprite.kind {
+                SpriteKind::Crab => &self.crab,
+                SpriteKind::SmallSpike => &self.small_spike,
+                SpriteKind::LargeSpike => &self.large_spike,
+                SpriteKind::Turret => {
+                    fill_circle(frame, Rect::new(px, py, tile, tile), DARKGRAY);
+                    continue;
+                }
+                SpriteKind::Projectile => {
+                    let r = tile * 0.3;
+                    let dest = Rect::new(px + r, py + r, tile - 2.0 * r, tile - 2.0 * r);
+                    fill_circle(frame, dest, ORANGE);
+                    continue;
+                }
+            };
+            let size = tile * sprite.kind.render_scale();
+            let dest = Rect::new(px, py, size, size);
+            blit(frame, image, full, dest, sprite.rotation, sprite.tint);
+        }
+    }
+
+    /// Normalised area of a tile in the tileset image
+    fn tile_rect(&self, id: u32) -> Rect {
+        let (w, h) = (self.tileset.width() as f32, self.tileset.height() as f32);
+        let tx = (id % self.tileset_columns) as f32 * UNIT_FRAME_SIZE;
+        let ty = (id / self.tileset_columns) as f32 * UNIT_FRAME_SIZE;
+
+        Rect::new(tx / w, ty / h, UNIT_FRAME_SIZE / w, UNIT_FRAME_SIZE / h)
+    }
+}
+
+/// Draws the `source` area (normalised) of `image` into `dest`, rotated
+/// around the dest center like `draw_texture_ex`, nearest neighbour sampled
+fn blit(frame: &mut Image, image: &Image, source: Rect, dest: Rect, rotation: f32, tint: Color) {
+    let center = dest.center();
+    let (sin, cos) = rotation.sin_cos();
+
+    // Bounding box of the rotated dest rect
+    let half = vec2(
+        (dest.w * cos).abs() + (dest.h * sin).abs(),
+        (dest.w * sin).abs() + (dest.h * cos).abs(),
+    ) / 2.0;
+    let min = (center - half).max(Vec2::ZERO);
+    let max = (center + half).min(vec2(frame.width() as f32, frame.height() as f32));
+
+    for py in min.y as u32..max.y.ceil() as u32 {
+        for px in min.x as u32..max.x.ceil() as u32 {
+            // Undo the rotation to find where the pixel lands in dest
+            let d = vec2(px as f32 + 0.5, py as f32 + 0.5) - center;
+            let local = vec2(d.x * cos + d.y * sin, -d.x * sin + d.y * cos);
+            let u = local.x / dest.w + 0.5;
+            let v = local.y / dest.h + 0.5;
+            if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+                continue;
+            }
+
+            let sx = ((source.x + u * source.w) * image.width() as f32) as u32;
+            let sy = ((source.y + v * source.h) * image.height() as f32) as u32;
+            let src = image.get_pixel(sx, sy);
+            if src.a <= 0.0 {
+                continue;
+            }
+
+            let dst = frame.get_pixel(px, py);
+            let a = src.a * tint.a;
+            frame.set_pixel(
+                px,
+                py,
+                Color::new(
+                    src.r * tint.r * a + dst.r * (1.0 - a),
+                    src.g * tint.g * a + dst.g * (1.0 - a),
+                    src.b * tint.b * a + dst.b * (1.0 - a),
+                    1.0,
+                ),
+            );
+        }
+    }
+}
+
+fn fill_circle(frame: &mut Image, dest: Rect, color: Color) {
+    let center = dest.center();
+    let radius = dest.w.min(dest.h) / 2.0;
+    let max = vec2(frame.width() as f32, frame.height() as f32);
+    let (min, max) = (
+        dest.point().max(Vec2::ZERO),
+        (dest.point() + dest.size()).min(max),
+    );
+    for py in min.y as u32..max.y.ceil() as u32 {
+        for px in min.x as u32..max.x.ceil() as u32 {
+            let p = vec2(px as f32 + 0.5, py as f32 + 0.5);
+            if p.distance(center) <= radius {
+                frame.set_pixel(px, py, color);
+            }
+        }
+    }
+}
+
+fn fill_rect(frame: &mut Image, dest: Rect, color: Color) {
+    let max = vec2(frame.width() as f32, frame.height() as f32);
+    let (min, max) = (
+        dest.point().max(Vec2::ZERO),
+        (dest.point() + dest.size()).min(max),
+    );
+    for py in min.y as u32..max.y.ceil() as u32 {
+        for px in min.x as u32..max.x.ceil() as u32 {
+            frame.set_pixel(px, py, color);
+        }
+    }
+}
+
+/// `Image::export_png` expects screen data, which is stored upside down
+pub(crate) fn save_png(frame: &Image, path: &str) -> io::Result<()> {
+    // `export_png` panics when it can't write, find out beforehand
+    fs::File::create(path)?;
+    let (w, h) = (frame.width() as u32, frame.height() as u32);
+    let mut flipped = Image::gen_image_color(w as u16, h as u16, BLACK);
+    for y in 0..h {
+        for x in 0..w {
+            flipped.set_pixel(x, h - y - 1, frame.get_pixel(x, y));
+        }
+    }
+
+    flipped.export_png(path);
+
+    Ok(())
+}
+
+/// Trains without a window for `num_generations`, then renders the
+/// episode of the best agent (or the first rooms of the population)
+/// to a numbered sequence of PNGs in `out_dir`
+pub fn record(out_dir: &str, num_generations: u32, is_grid: bool) {
+    init_resources_headless();
+    fs::create_dir_all(out_dir).expect("Failed to create the output dir");
+
+    let editor = Editor::new();
+    let mut simulation = Simulation::new();
+    let mut stats = simulation.step(&editor);
+    while stats.generation_count <= num_generations {
+        stats = simulation.step(&editor);
+    }
+    println!("Trained {} generations", num_generations);
+
+    // After selection the best games of the last generation come first
+    let recorder = Recorder::new(RECORD_SCALE);
+    let mut games: Vec<Game> = match is_grid {
+        true => simulation.population().games().to_vec(),
+        false => simulation.population().games()[..1].to_vec(),
+    };
+    games.truncate(RECORD_GRID_SIZE * RECORD_GRID_SIZE);
+    fs::write(format!("{}/genome.txt", out_dir), games[0].moves_string())
+        .expect("Failed to save the genome");
+
+    for frame_count in 0..=NUM_FRAMES {
+        let frame = match is_grid {
+            true => recorder.render_grid(&games),
+            false => recorder.render_game(&games[0]),
+        };
+        save_png(&frame, &format!("{}/frame_{:04}.png", out_dir, frame_count))
+            .expect("Failed to save the frame");
+
+        games.iter_mut().for_each(|g| g.update(frame_count));
+    }
+    println!("Saved {} frames to {}", NUM_FRAMES + 1, out_dir);
+}
@@ -1,14 +1,23 @@
+use std::sync::{Arc, RwLock};
+
 use macroquad::prelude::*;
 use macroquad_tiled::Map;
 use once_cell::sync::OnceCell;
 
-use crate::level::LevelInfo;
+use crate::generator::Room;
+use crate::level::{self, LevelInfo};
 use crate::*;
 
-// This is a OnceCell static Global var
-pub static RESOURCES: OnceCell<Resources> = OnceCell::new();
+// These are static Global vars, the level can be swapped out at runtime
+pub static RESOURCES: ResourceCell = ResourceCell::new();
 pub static TEXTURES: OnceCell<Textures> = OnceCell::new();
 
+/// Works like a `OnceCell`, except the level can be replaced (see
+/// `load_room`). Games hold on to the resources of their level, a replaced
+/// level is dropped along with the last game that uses it.
+/// Holds every level trained in the batch, the first is `LVL_MAP_PATH`
+pub struct ResourceCell(RwLock<Vec<Arc<Resources>>>);
+
 pub struct Resources {
     pub lvl_map: Map,
     pub lvl_background_sprite: Texture2D,
@@ -33,22 +42,185 @@ pub async fn init_resources() {
         Ok(_) => println!("Resources init successfull"),
         Err(_) => panic!("Failed to load Resources"),
     };
+    for (map_path, background_path) in LVL_EXTRA_LEVELS {
+        RESOURCES.push(Resources::load(map_path, background_path).await);
+    }
     match TEXTURES.set(textures) {
         Ok(_) => println!("Textures init successfull"),
         Err(_) => panic!("Failed to load Textures"),
     };
 }
 
+/// Loads the level without a graphics context, textures are left empty
+pub fn init_resources_headless() {
+    if RESOURCES
+        .set(Resources::new_headless(LVL_MAP_PATH))
+        .is_err()
+    {
+        panic!("Failed to load Resources");
+    }
+    for (map_path, _) in LVL_EXTRA_LEVELS {
+        RESOURCES.push(Resources::new_headless(map_path));
+    }
+    if TEXTURES.set(Textures::empty()).is_err() {
+        panic!("Failed to load Textures");
+    }
+}
+
+/// Swaps one of the levels for a room, the simulation has to be restarted
+/// after this
+pub fn load_room(room: &Room, level: usize) {
+    RESOURCES.replace_level(level, Resources::from_room(room));
+}
+
+/// Map and background of every level, the main one first
+pub fn level_paths() -> impl Iterator<Item = (&'static str, &'static str)> {
+    std::iter::once((LVL_MAP_PATH, LVL_BACKGROUND_SPRITE)).chain(LVL_EXTRA_LEVELS.iter().copied())
+}
+
+/// Reads the files of every level again. If any of them can't be loaded
+/// (Tiled might still be writing it) all the old levels are kept
+pub fn reload_levels() -> Result<(), String> {
+    let levels = level_paths()
+        .map(|(map_path, background_path)| Resources::from_files(map_path, background_path))
+        .collect::<Result<Vec<_>, _>>()?;
+    RESOURCES.replace_levels(levels);
+
+    Ok(())
+}
+
+impl ResourceCell {
+    const fn new() -> Self {
+        Self(RwLock::new(Vec::new()))
+    }
+
+    /// The main level
+    pub fn get(&self) -> Option<Arc<Resources>> {
+        self.level(0)
+    }
+
+    pub fn level(&self, idx: usize) -> Option<Arc<Resources>> {
+        self.0.read().unwrap().get(idx).cloned()
+    }
+
+    pub fn num_levels(&self) -> usize {
+        self.0.read().unwrap().len()
+    }
+
+    pub fn set(&self, resources: Resources) -> Result<(), Box<Resources>> {
+        let mut cell = self.0.write().unwrap();
+        if !cell.is_empty() {
+            return Err(Box::new(resources));
+        }
+
+        cell.push(Arc::new(resources));
+        Ok(())
+    }
+
+    /// Adds a level to train alongside the main one
+    pub fn push(&self, resources: Resources) {
+        self.0.write().unwrap().push(Arc::new(resources));
+    }
+
+    /// Replaces the main level
+    pub fn replace(&self, resources: Resources) {
+        self.replace_level(0, resources);
+    }
+
+    /// Replaces a level, or adds it after the last one
+    pub fn replace_level(&self, idx: usize, resources: Resources) {
+        let mut cell = self.0.write().unwrap();
+        let resources = Arc::new(resources);
+        match cell.get_mut(idx) {
+            Some(level) => *level = resources,
+            None => cell.push(resources),
+        }
+    }
+
+    /// Replaces the levels in order, from the main one on
+    pub fn replace_levels(&self, levels: Vec<Resources>) {
+        for (idx, resources) in levels.into_iter().enumerate() {
+            self.replace_level(idx, resources);
+        }
+    }
+}
+
 impl Resources {
     async fn new() -> Self {
+        Resources::load(LVL_MAP_PATH, LVL_BACKGROUND_SPRITE).await
+    }
+
+    async fn load(map_path: &str, background_path: &str) -> Self {
         // Load level components
-        let lvl_background_sprite = load_texture(LVL_BACKGROUND_SPRITE).await.unwrap();
+        let lvl_background_sprite = load_texture(background_path).await.unwrap();
         lvl_background_sprite.set_filter(FilterMode::Nearest);
-        let tiled_map_json = load_string(LVL_MAP_PATH).await.unwrap();
+        let tiled_map_json = load_string(map_path).await.unwrap();
         let tileset = Textures::get_texture(TILESET_PATH).await;
-        let lvl_map =
-            macroquad_tiled::load_map(&tiled_map_json, &[(TILE_SET_NAME, tileset)], &[]).unwrap();
-        let lvl_info = LevelInfo::new(&lvl_map);
+
+        Resources::from_json(&tiled_map_json, tileset, lvl_background_sprite)
+            .unwrap_or_else(|e| panic!("Failed to load {}: {}", map_path, e))
+    }
+
+    fn from_json(
+        tiled_map_json: &str,
+        tileset: Texture2D,
+        lvl_background_sprite: Texture2D,
+    ) -> Result<Self, String> {
+        let lvl_map = level::load_map(tiled_map_json, &[(TILE_SET_NAME, tileset)])?;
+        let lvl_info = LevelInfo::new(&lvl_map)?;
+
+        Ok(Self {
+            lvl_map,
+            lvl_background_sprite,
+            lvl_info,
+        })
+    }
+
+    /// Like `load`, but loaded synchronously and reusing the loaded tileset.
+    /// Fails instead of panicking on files that can't be read
+    fn from_files(map_path: &str, background_path: &str) -> Result<Self, String> {
+        let bytes = std::fs::read(background_path)
+            .map_err(|e| format!("Failed to read {}: {}", background_path, e))?;
+        let background = image::load_from_memory(&bytes)
+            .map_err(|e| format!("Failed to decode {}: {}", background_path, e))?
+            .to_rgba8();
+        let lvl_background_sprite = Texture2D::from_rgba8(
+            background.width() as u16,
+            background.height() as u16,
+            background.as_raw(),
+        );
+        lvl_background_sprite.set_filter(FilterMode::Nearest);
+        let tiled_map_json = std::fs::read_to_string(map_path)
+            .map_err(|e| format!("Failed to read {}: {}", map_path, e))?;
+
+        Resources::from_json(&tiled_map_json, Resources::tileset(), lvl_background_sprite)
+            .map_err(|e| format!("Failed to load {}: {}", map_path, e))
+    }
+
+    fn tileset() -> Texture2D {
+        RESOURCES
+            .get()
+            .unwrap()
+            .lvl_map
+            .tilesets
+            .values()
+            .next()
+            .map(|t| t.texture)
+            .unwrap()
+    }
+
+    /// Level resources of an edited or generated room, needs a graphics
+    /// context for the background texture
+    fn from_room(room: &Room) -> Self {
+        let lvl_map = macroquad_tiled::load_map(
+            &room.to_tiled().to_json(),
+            &[(TILE_SET_NAME, Resources::tileset())],
+            &[],
+        )
+        .unwrap();
+        let lvl_info = LevelInfo::new(&lvl_map).unwrap();
+        let lvl_background_sprite = Texture2D::from_image(&room.render_background());
+        lvl_background_sprite.set_filter(FilterMode::Nearest);
 
         Self {
             lvl_map,
@@ -56,6 +228,13 @@ impl Resources {
             lvl_info,
         }
     }
+
+    fn new_headless(map_path: &str) -> Self {
+        let tiled_map_json = std::fs::read_to_string(map_path).unwrap();
+
+        Resources::from_json(&tiled_map_json, Texture2D::empty(), Texture2D::empty())
+            .unwrap_or_else(|e| panic!("Failed to load {}: {}", map_path, e))
+    }
 }
 
 impl Textures {
@@ -73,6 +252,20 @@ impl Textures {
         }
     }
 
+    fn empty() -> Self {
+        Self {
+            agent_texture: Texture2D::empty(),
+            agent_sleep1_texture: Texture2D::empty(),
+            agent_sleep2_texture: Texture2D::empty(),
+            agent_sleep3_texture: Texture2D::empty(),
+
+            key_texture: Texture2D::empty(),
+            crab_texture: Texture2D::empty(),
+            small_spike_texture: Texture2D::empty(),
+            large_spike_texture: Texture2D::empty(),
+        }
+    }
+
     async fn get_texture(path: &str) -> Texture2D {
         let texture = load_texture(path).await.unwrap();
         texture.set_filter(FilterMode::Nearest);
@@ -0,0 +1,97 @@
+use std::time::{Duration, Instant};
+
+/// Runs simulation steps at a target rate, whatever the render frame rate.
+/// Each frame the steps due since the last one are run, but never for longer
+/// than the time budget so the ui stays responsive. Steps that don't fit in
+/// the budget are dropped, the simulation then runs as fast as it can
+pub struct Scheduler {
+    budget: Duration,
+    // Steps due but not run yet, a fraction of a step carries over
+    owed: f32,
+    last_run: Instant,
+    // Steps run in the last `window_time` seconds, to measure the actual rate
+    num_window_steps: usize,
+    window_time: f32,
+    measured_rate: f32,
+}
+
+impl Scheduler {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            owed: 0.0,
+            last_run: Instant::now(),
+            num_window_steps: 0,
+            window_time: 0.0,
+            measured_rate: 0.0,
+        }
+    }
+
+    /// Calls `step` for the steps due at `steps_per_second`, an infinite
+    /// rate runs steps for the whole budget. `step` returns whether it ran,
+    /// once it doesn't (paused, halted) the rest is dropped. Returns the
+    /// number of steps run
+    pub fn run(&mut self, steps_per_second: f32, step: impl FnMut() -> bool) -> usize {
+        let now = Instant::now();
+        let dt = now - self.last_run;
+        self.last_run = now;
+        self.run_for(dt, steps_per_second, step)
+    }
+
+    /// Like `run`, with the time since the last run given instead of read
+    /// from the clock
+    pub fn run_for(
+        &mut self,
+        dt: Duration,
+        steps_per_second: f32,
+        step: impl FnMut() -> bool,
+    ) -> usize {
+        let start = Instant::now();
+        self.run_timed(dt, steps_per_second, || start.elapsed(), step)
+    }
+
+    /// Like `run_for`, with the time spent stepping so far given by
+    /// `elapsed` instead of read from the clock
+    pub fn run_timed(
+        &mut self,
+        dt: Duration,
+        steps_per_second: f32,
+        elapsed: impl Fn() -> Duration,
+        mut step: impl FnMut() -> bool,
+    ) -> usize {
+        // Long frames (a window drag, a breakpoint) don't pile up steps
+        let dt = dt.as_secs_f32().min(0.25);
+        self.owed = match steps_per_second.is_finite() {
+            true => self.owed + steps_per_second * dt,
+            false => f32::INFINITY,
+        };
+
+        let mut num_steps = 0;
+        while self.owed >= 1.0 && elapsed() < self.budget {
+            if !step() {
+                self.owed = 0.0;
+                break;
+            }
+            self.owed -= 1.0;
+            num_steps += 1;
+        }
+        if self.owed >= 1.0 {
+            self.owed = 0.0;
+        }
+
+        self.num_window_steps += num_steps;
+        self.window_time += dt;
+        if self.window_time >= 1.0 {
+            self.measured_rate = self.num_window_steps as f32 / self.window_time;
+            self.num_window_steps = 0;
+            self.window_time = 0.0;
+        }
+
+        num_steps
+    }
+
+    /// Steps actually run per second, over the last second
+    pub fn measured_rate(&self) -> f32 {
+        self.measured_rate
+    }
+}
@@ -1,52 +1,393 @@
 use macroquad::prelude::*;
 
+use crate::agent::ActionSpace;
+use crate::demo::Demo;
 use crate::editor::Editor;
+use crate::game::{FitnessBreakdown, Game};
+use crate::minimap;
 use crate::population::Population;
 use crate::*;
 
 pub struct Simulation {
-    population: Population,
+    // One per level, the first trains on the main level
+    populations: Vec<Population>,
+    action_space: ActionSpace,
     frame_count: usize,
     generation_count: u32,
+    // Copy of an inspected game played on its own in the single view, with
+    // its own frame count
+    replay: Option<(Game, usize)>,
+    // Run played by hand in play mode, the training waits meanwhile
+    demo: Option<Demo>,
+    // Why the simulation stopped itself, see `take_halt`
+    halt: Option<Halt>,
+    // Whether any agent got the key since the start, for the key breakpoint
+    is_key_found: bool,
+    best_fitness: f32,
+}
+
+/// How far the simulation runs before it pauses, picked in the debug controls
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StepTarget {
+    Frame,
+    // Every game has played its last frame, before the selection
+    Episode,
+    Generation,
+    // An agent reached the door
+    Solved,
+    UntilGeneration(u32),
+}
+
+/// Why the simulation paused itself
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Halt {
+    Reached(StepTarget),
+    KeyCollected,
+    BestFitness(f32),
+}
+
+/// What the inspector shows of a game picked in the multi view
+pub struct GameInspection<'a> {
+    pub idx: usize,
+    pub level: usize,
+    pub game: &'a Game,
+    pub fitness: FitnessBreakdown,
+    pub is_pinned: bool,
+    pub is_replaying: bool,
 }
 
 pub struct SimulationStats {
     pub frame_count: usize,
     pub generation_count: u32,
+    // Agents killed by enemies in the last generation
+    pub num_deaths: u32,
+    // Demos waiting for the next generation
+    pub num_queued_demos: usize,
+    // Measured by the scheduler
+    pub steps_per_second: f32,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Games of each level, split evenly with the first levels getting one more
+/// each for the remainder. Every level gets at least one game
+pub fn split_games(num_games: usize, num_levels: usize) -> Vec<usize> {
+    let (per_level, remainder) = (num_games / num_levels, num_games % num_levels);
+    (0..num_levels)
+        .map(|level| (per_level + usize::from(level < remainder)).max(1))
+        .collect()
 }
 
 impl Simulation {
     pub fn new() -> Self {
+        Simulation::with_action_space(ACTION_SPACE)
+    }
+
+    pub fn with_action_space(action_space: ActionSpace) -> Self {
+        let num_games = split_games(NUM_GAMES as usize, RESOURCES.num_levels());
         Self {
-            population: Population::new(),
+            populations: num_games
+                .into_iter()
+                .enumerate()
+                .map(|(level, num_games)| {
+                    Population::with_action_space(level, num_games, action_space)
+                })
+                .collect(),
+            action_space,
             frame_count: 0,
             generation_count: 1,
+            replay: None,
+            demo: None,
+            halt: None,
+            is_key_found: false,
+            best_fitness: 0.0,
+        }
+    }
+
+    /// Keys of the play mode, to be called once per rendered frame
+    pub fn handle_user_input(&mut self, editor: &Editor) {
+        if let Some(demo) = self.demo.as_mut().filter(|_| !editor.is_rebinding()) {
+            demo.handle_user_input(&editor.input);
         }
     }
 
+    /// One step of the training, unless it's paused, waiting for a demo or
+    /// halted. Stops at the step target and the breakpoints of the editor
     pub fn update(&mut self, editor: &Editor) -> Option<SimulationStats> {
-        if editor.settings.is_pause {
+        if self.demo.is_some() || self.halt.is_some() || editor.settings.is_pause {
             return None;
         }
+        let settings = &editor.settings;
 
-        self.population.update(self.frame_count, editor);
-        self.frame_count += 1;
+        // An episode halted at its end goes on with the selection
+        if self.is_episode_over(editor) {
+            self.start_new_generation(!settings.is_random_ai);
+            self.check_generation(editor);
+            return Some(self.stats());
+        }
+
+        self.play_frame(editor);
+        if settings.step_target == Some(StepTarget::Frame) {
+            self.halt = Some(Halt::Reached(StepTarget::Frame));
+        }
+        let is_key_collected = self.any_game(|g| g.is_key_collected);
+        if is_key_collected && !self.is_key_found {
+            self.is_key_found = true;
+            if settings.is_break_on_key {
+                self.halt = Some(Halt::KeyCollected);
+            }
+        }
+        if settings.step_target == Some(StepTarget::Solved) && self.any_game(|g| g.is_complete) {
+            self.halt = Some(Halt::Reached(StepTarget::Solved));
+        }
 
-        if self.frame_count >= NUM_FRAMES && editor.settings.is_ai_enabled {
+        if self.is_episode_over(editor) {
+            match settings.step_target {
+                Some(StepTarget::Episode) => self.halt = Some(Halt::Reached(StepTarget::Episode)),
+                _ if self.halt.is_none() => {
+                    self.start_new_generation(!settings.is_random_ai);
+                    self.check_generation(editor);
+                }
+                _ => (),
+            }
+        }
+
+        Some(self.stats())
+    }
+
+    /// Why the simulation paused itself, if it did. The simulation goes on
+    /// once this is taken, the caller should pause it
+    pub fn take_halt(&mut self) -> Option<Halt> {
+        self.halt.take()
+    }
+
+    /// Advances the simulation by one frame without reading any input or
+    /// stopping at breakpoints, this is what the headless tools drive
+    pub fn step(&mut self, editor: &Editor) -> SimulationStats {
+        self.play_frame(editor);
+        if self.is_episode_over(editor) {
             self.start_new_generation(!editor.settings.is_random_ai);
         }
 
-        Some(SimulationStats {
+        self.stats()
+    }
+
+    fn play_frame(&mut self, editor: &Editor) {
+        self.populations
+            .iter_mut()
+            .for_each(|p| p.update(self.frame_count, editor));
+        self.frame_count += 1;
+
+        // The replay loops over its episode
+        if let Some((game, frame)) = self.replay.as_mut() {
+            game.update(*frame);
+            *frame += 1;
+            if *frame >= NUM_FRAMES {
+                *game = game.restart();
+                *frame = 0;
+            }
+        }
+    }
+
+    fn is_episode_over(&self, editor: &Editor) -> bool {
+        self.frame_count >= NUM_FRAMES && editor.settings.is_ai_enabled
+    }
+
+    fn any_game(&self, f: impl Fn(&Game) -> bool) -> bool {
+        self.populations.iter().any(|p| p.games().iter().any(&f))
+    }
+
+    /// Breakpoints and step targets checked once a generation starts
+    fn check_generation(&mut self, editor: &Editor) {
+        let settings = &editor.settings;
+        let best_fitness = self
+            .populations
+            .iter()
+            .map(|p| p.best_fitness())
+            .fold(0.0, f32::max);
+        if best_fitness > self.best_fitness {
+            self.best_fitness = best_fitness;
+            if settings.is_break_on_best_fitness {
+                self.halt = Some(Halt::BestFitness(best_fitness));
+            }
+        }
+
+        match settings.step_target {
+            Some(StepTarget::Generation) => {
+                self.halt = Some(Halt::Reached(StepTarget::Generation));
+            }
+            Some(StepTarget::UntilGeneration(n)) if self.generation_count >= n => {
+                self.halt = Some(Halt::Reached(StepTarget::UntilGeneration(n)));
+            }
+            _ => (),
+        }
+    }
+
+    fn stats(&self) -> SimulationStats {
+        SimulationStats {
             frame_count: self.frame_count,
             generation_count: self.generation_count,
+            num_deaths: self.populations.iter().map(|p| p.num_deaths()).sum(),
+            num_queued_demos: self.num_queued_demos(),
+            steps_per_second: 0.0,
+        }
+    }
+
+    /// Keeps training the current genomes on a reloaded level, the
+    /// generation starts over
+    pub fn reload_level(&mut self) {
+        self.populations.iter_mut().for_each(|p| p.reload_level());
+        self.frame_count = 0;
+    }
+
+    /// Population of the main level
+    pub fn population(&self) -> &Population {
+        &self.populations[0]
+    }
+
+    pub fn populations(&self) -> &[Population] {
+        &self.populations
+    }
+
+    /// Game at a world position of the multi view, counted across all levels
+    pub fn game_at(&self, pos: Vec2) -> Option<usize> {
+        (0..self.num_games()).find(|idx| {
+            self.game_rect(*idx)
+                .map(|r| r.contains(pos))
+                .unwrap_or(false)
+        })
+    }
+
+    /// Area of a game in the multi view, counted across all levels
+    pub fn game_rect(&self, idx: usize) -> Option<Rect> {
+        let (level, idx) = self.locate(idx)?;
+        Some(self.populations[level].game_rect(idx))
+    }
+
+    /// Population and index in it of a game counted across all levels
+    fn locate(&self, mut idx: usize) -> Option<(usize, usize)> {
+        for (level, p) in self.populations.iter().enumerate() {
+            if idx < p.games().len() {
+                return Some((level, idx));
+            }
+            idx -= p.games().len();
+        }
+
+        None
+    }
+
+    pub fn inspect(&self, idx: usize) -> Option<GameInspection<'_>> {
+        let (level, local_idx) = self.locate(idx)?;
+        let population = &self.populations[level];
+        Some(GameInspection {
+            idx,
+            level,
+            game: &population.games()[local_idx],
+            fitness: population.fitness_breakdown(local_idx),
+            is_pinned: population.is_pinned(local_idx),
+            is_replaying: self.replay.is_some(),
         })
     }
 
+    pub fn toggle_pin(&mut self, idx: usize) {
+        if let Some((level, idx)) = self.locate(idx) {
+            self.populations[level].toggle_pin(idx);
+        }
+    }
+
+    /// Plays the game again from the start in the single view, see
+    /// `Game::restart`
+    pub fn replay(&mut self, idx: usize) {
+        if let Some((level, idx)) = self.locate(idx) {
+            let game = self.populations[level].games()[idx].restart();
+            self.replay = Some((game, 0));
+        }
+    }
+
+    pub fn stop_replay(&mut self) {
+        self.replay = None;
+    }
+
+    /// Starts a run played by hand on the level of the single view
+    pub fn start_demo(&mut self, editor: &Editor) {
+        self.demo = Some(Demo::with_action_space(
+            self.selected_idx(editor),
+            self.action_space,
+        ));
+    }
+
+    pub fn stop_demo(&mut self) {
+        self.demo = None;
+    }
+
+    pub fn demo(&self) -> Option<&Demo> {
+        self.demo.as_ref()
+    }
+
+    /// Starts the run played by hand over
+    pub fn retry_demo(&mut self) {
+        if let Some(demo) = self.demo.as_mut() {
+            *demo = Demo::with_action_space(demo.level(), self.action_space);
+        }
+    }
+
+    /// Queues the run played by hand, and mutations of it, for the next
+    /// generation of its level. Play starts over for another demo
+    pub fn use_demo(&mut self) {
+        if let Some(demo) = self.demo.as_ref().filter(|d| d.frame_count() > 0) {
+            self.populations[demo.level()].add_demo(demo.genome());
+        }
+        self.retry_demo();
+    }
+
+    pub fn num_queued_demos(&self) -> usize {
+        self.populations.iter().map(|p| p.num_queued_demos()).sum()
+    }
+
+    pub fn num_games(&self) -> usize {
+        self.populations.iter().map(|p| p.games().len()).sum()
+    }
+
+    /// Population shown in the single view
+    pub fn selected(&self, editor: &Editor) -> &Population {
+        &self.populations[self.selected_idx(editor)]
+    }
+
+    fn selected_idx(&self, editor: &Editor) -> usize {
+        editor
+            .settings
+            .selected_level
+            .min(self.populations.len() - 1)
+    }
+
+    /// World position of the agent the camera follows, the first game of
+    /// the single view
+    pub fn focus_pos(&self, editor: &Editor) -> Option<Vec2> {
+        if editor.settings.is_show_multiple {
+            return None;
+        }
+
+        let scale_factor = UNIT_FRAME_SIZE * FRAME_SCALE;
+        let game = match (&self.demo, &self.replay) {
+            (Some(demo), _) => demo.game(),
+            (None, Some((game, _))) => game,
+            (None, None) => &self.selected(editor).games()[0],
+        };
+        let (x, y) = *game.get_current_agent_pos();
+        Some(vec2(x as f32 + 0.5, y as f32 + 0.5) * scale_factor)
+    }
+
     fn start_new_generation(&mut self, is_selection: bool) {
-        if is_selection {
-            self.population.selection();
-        } else {
-            self.population = Population::new();
+        for p in self.populations.iter_mut() {
+            if is_selection {
+                p.selection();
+            } else {
+                *p = Population::with_action_space(p.level(), p.games().len(), self.action_space);
+            }
         }
 
         self.frame_count = 0;
@@ -58,7 +399,87 @@ impl Simulation {
             return;
         }
 
-        self.population.draw(editor);
+        if editor.settings.is_show_multiple {
+            self.populations.iter().for_each(|p| p.draw_multi(editor));
+            self.draw_highlights(editor);
+            return;
+        }
+
+        let solo = match (&self.demo, &self.replay) {
+            (Some(demo), _) => Some(demo.game()),
+            (None, Some((game, _))) => Some(game),
+            (None, None) => None,
+        };
+        if let Some(game) = solo {
+            game.draw(0.0, 0.0);
+            if editor.settings.is_show_minimap {
+                let games = std::slice::from_ref(game);
+                minimap::draw(games, editor.visible_rect(), &editor.camera());
+            }
+            return;
+        }
+
+        let population = self.selected(editor);
+        population.draw_single(editor);
+        if editor.settings.is_show_minimap {
+            minimap::draw(population.games(), editor.visible_rect(), &editor.camera());
+        }
+    }
+
+    /// Outlines the pinned and the inspected games in the multi view
+    fn draw_highlights(&self, editor: &Editor) {
+        let outline = |rect: Rect, color: Color| {
+            let pad = MULTI_VIEW_PADDING / 4.0;
+            draw_rectangle_lines(
+                rect.x - pad,
+                rect.y - pad,
+                rect.w + pad * 2.0,
+                rect.h + pad * 2.0,
+                pad,
+                color,
+            );
+        };
+
+        for p in self.populations.iter() {
+            (0..p.games().len())
+                .filter(|idx| p.is_pinned(*idx))
+                .for_each(|idx| outline(p.game_rect(idx), GOLD));
+        }
+        if let Some(rect) = editor
+            .settings
+            .inspected_game
+            .and_then(|i| self.game_rect(i))
+        {
+            outline(rect, SKYBLUE);
+        }
+    }
+}
+
+impl StepTarget {
+    /// Only frames are stepped with the AI disabled, the games don't play
+    /// so episodes never end
+    pub fn is_needing_ai(self) -> bool {
+        self != StepTarget::Frame
+    }
+}
+
+impl Halt {
+    pub fn describe(&self) -> String {
+        match self {
+            Halt::Reached(StepTarget::Frame) => "Stepped a frame".to_string(),
+            Halt::Reached(StepTarget::Episode) => "End of the episode".to_string(),
+            Halt::Reached(StepTarget::Generation) => "New generation".to_string(),
+            Halt::Reached(StepTarget::Solved) => "Solved, an agent escaped".to_string(),
+            Halt::Reached(StepTarget::UntilGeneration(n)) => format!("Reached generation {}", n),
+            Halt::KeyCollected => "First agent got the key".to_string(),
+            Halt::BestFitness(fitness) => format!("Best fitness improved to {:.2}", fitness),
+        }
+    }
+}
+
+impl Default for SimulationStats {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -67,6 +488,9 @@ impl SimulationStats {
         Self {
             frame_count: 1,
             generation_count: 1,
+            num_deaths: 0,
+            num_queued_demos: 0,
+            steps_per_second: 0.0,
         }
     }
 }
@@ -0,0 +1,311 @@
+use crate::export::json_string;
+
+/// Element of an xml document, only what Tiled writes to `.tmx` files
+#[derive(Debug, Default)]
+struct XmlNode {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<XmlNode>,
+    text: String,
+}
+
+impl XmlNode {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn attr_or<'a>(&'a self, name: &str, default: &'a str) -> &'a str {
+        self.attr(name).unwrap_or(default)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlNode> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    fn child(&self, name: &str) -> Option<&XmlNode> {
+        self.children.iter().find(|c| c.name == name)
+    }
+}
+
+/// Minimal xml parser, no namespaces or doctypes, which Tiled doesn't use
+fn parse_xml(xml: &str) -> Result<XmlNode, String> {
+    let mut stack = vec![XmlNode::default()];
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        let text = unescape(rest[..start].trim());
+        if !text.is_empty() {
+            stack.last_mut().unwrap().text.push_str(&text);
+        }
+        rest = &rest[start..];
+
+        // Declarations and comments
+        if rest.starts_with("<?") || rest.starts_with("<!--") {
+            let end = match rest.starts_with("<?") {
+                true => rest.find("?>").map(|i| i + 2),
+                false => rest.find("-->").map(|i| i + 3),
+            };
+            rest = &rest[end.ok_or("Unclosed xml tag")?..];
+            continue;
+        }
+
+        let end = rest.find('>').ok_or("Unclosed xml tag")?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            // The root always stays on the stack
+            if stack.len() == 1 {
+                return Err(format!("Unopened xml tag {}", name));
+            }
+            let node = stack.pop().unwrap();
+            if node.name != name.trim() {
+                return Err(format!("Mismatched xml tags {} and {}", node.name, name));
+            }
+            stack.last_mut().unwrap().children.push(node);
+            continue;
+        }
+
+        let is_self_closing = tag.ends_with('/');
+        let tag = tag.trim_end_matches('/');
+        let (name, mut attrs_str) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        let mut node = XmlNode {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        while let Some(eq) = attrs_str.find('=') {
+            let attr_name = attrs_str[..eq].trim().to_string();
+            let after = attrs_str[eq + 1..].trim_start();
+            let quote = after.chars().next().ok_or("Missing xml attribute value")?;
+            let value_end = after[1..].find(quote).ok_or("Unclosed xml attribute")? + 1;
+            node.attrs.push((attr_name, unescape(&after[1..value_end])));
+            attrs_str = &after[value_end + 1..];
+        }
+
+        match is_self_closing {
+            true => stack.last_mut().unwrap().children.push(node),
+            false => stack.push(node),
+        }
+    }
+
+    if stack.len() > 1 {
+        return Err("Unclosed xml tags".to_string());
+    }
+    let mut root = stack.pop().unwrap();
+    root.children
+        .pop()
+        .ok_or_else(|| "Empty xml document".to_string())
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Converts a Tiled `.tmx` map to the json format, so it can be loaded like
+/// the `.json` maps. Tilesets have to be embedded in the map, and tile
+/// layers saved as csv or uncompressed base64
+pub fn tmx_to_json(tmx: &str) -> Result<String, String> {
+    let map = parse_xml(tmx)?;
+    if map.name != "map" {
+        return Err("Not a tmx map".to_string());
+    }
+
+    let layers: Vec<String> = map
+        .children
+        .iter()
+        .filter(|c| c.name == "layer" || c.name == "objectgroup")
+        .map(|layer| match layer.name.as_str() {
+            "layer" => tile_layer_json(layer),
+            _ => Ok(object_layer_json(layer)),
+        })
+        .collect::<Result<_, _>>()?;
+    let tilesets: Vec<String> = map
+        .children_named("tileset")
+        .map(tileset_json)
+        .collect::<Result<_, _>>()?;
+
+    Ok(format!(
+        r#"{{"height":{},"infinite":false,"layers":[{}],"orientation":{},"renderorder":{},"tileheight":{},"tilesets":[{}],"tilewidth":{},"type":"map","version":{},"width":{}}}"#,
+        map.attr_or("height", "0"),
+        layers.join(","),
+        json_string(map.attr_or("orientation", "orthogonal")),
+        json_string(map.attr_or("renderorder", "right-down")),
+        map.attr_or("tileheight", "8"),
+        tilesets.join(","),
+        map.attr_or("tilewidth", "8"),
+        json_string(map.attr_or("version", "1.10")),
+        map.attr_or("width", "0")
+    ))
+}
+
+fn tile_layer_json(layer: &XmlNode) -> Result<String, String> {
+    let data = layer.child("data").ok_or("Tile layer without data")?;
+    let gids: Vec<String> = match data.attr("encoding") {
+        Some("csv") => data
+            .text
+            .split(',')
+            .map(|g| g.trim().to_string())
+            .filter(|g| !g.is_empty())
+            .collect(),
+        Some("base64") if data.attr("compression").is_none() => decode_base64(&data.text)
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]).to_string())
+            .collect(),
+        Some(encoding) => {
+            return Err(format!(
+                "Unsupported tmx layer data ({} {}), save the layer as csv",
+                encoding,
+                data.attr_or("compression", "")
+            ))
+        }
+        // Plain xml, a <tile gid=".."/> per tile
+        None => data
+            .children_named("tile")
+            .map(|t| t.attr_or("gid", "0").to_string())
+            .collect(),
+    };
+
+    Ok(format!(
+        r#"{{"data":[{}],"height":{},"name":{},"opacity":1,"properties":{{}},"type":"tilelayer","visible":{},"width":{},"x":0,"y":0}}"#,
+        gids.join(","),
+        layer.attr_or("height", "0"),
+        json_string(layer.attr_or("name", "")),
+        layer.attr_or("visible", "1") != "0",
+        layer.attr_or("width", "0")
+    ))
+}
+
+fn object_layer_json(layer: &XmlNode) -> String {
+    let objects: Vec<String> = layer
+        .children_named("object")
+        .map(|object| {
+            // Tiled 1.9 renamed the object type to class
+            let ty = object.attr("class").or(object.attr("type")).unwrap_or("");
+            let gid = match object.attr("gid") {
+                Some(gid) => format!(r#""gid":{},"#, gid),
+                None => String::new(),
+            };
+            let polyline = match object.child("polyline") {
+                Some(polyline) => {
+                    let points: Vec<String> = polyline
+                        .attr_or("points", "")
+                        .split_whitespace()
+                        .filter_map(|p| p.split_once(','))
+                        .map(|(x, y)| format!(r#"{{"x":{},"y":{}}}"#, x, y))
+                        .collect();
+                    format!(r#""polyline":[{}],"#, points.join(","))
+                }
+                None => String::new(),
+            };
+
+            format!(
+                r#"{{{}"height":{},"id":{},"name":{},{}"properties":[{}],"rotation":0,"type":{},"visible":true,"width":{},"x":{},"y":{}}}"#,
+                gid,
+                object.attr_or("height", "0"),
+                object.attr_or("id", "0"),
+                json_string(object.attr_or("name", "")),
+                polyline,
+                properties_json(object).join(","),
+                json_string(ty),
+                object.attr_or("width", "0"),
+                object.attr_or("x", "0"),
+                object.attr_or("y", "0")
+            )
+        })
+        .collect();
+
+    format!(
+        r#"{{"draworder":"topdown","name":{},"objects":[{}],"opacity":1,"type":"objectgroup","visible":{},"x":0,"y":0}}"#,
+        json_string(layer.attr_or("name", "")),
+        objects.join(","),
+        layer.attr_or("visible", "1") != "0"
+    )
+}
+
+fn tileset_json(tileset: &XmlNode) -> Result<String, String> {
+    if let Some(source) = tileset.attr("source") {
+        return Err(format!(
+            "External tileset {} isn't supported, embed it in the map",
+            source
+        ));
+    }
+
+    let image = tileset.child("image").ok_or("Tileset without an image")?;
+    let tiles: Vec<String> = tileset
+        .children_named("tile")
+        .map(|tile| {
+            format!(
+                r#"{{"id":{},"properties":[{}]}}"#,
+                tile.attr_or("id", "0"),
+                properties_json(tile).join(",")
+            )
+        })
+        .collect();
+
+    Ok(format!(
+        r#"{{"columns":{},"firstgid":{},"image":{},"imageheight":{},"imagewidth":{},"margin":{},"name":{},"spacing":{},"tilecount":{},"tileheight":{},"tiles":[{}],"tilewidth":{}}}"#,
+        tileset.attr_or("columns", "1"),
+        tileset.attr_or("firstgid", "1"),
+        json_string(image.attr_or("source", "")),
+        image.attr_or("height", "0"),
+        image.attr_or("width", "0"),
+        tileset.attr_or("margin", "0"),
+        json_string(tileset.attr_or("name", "")),
+        tileset.attr_or("spacing", "0"),
+        tileset.attr_or("tilecount", "0"),
+        tileset.attr_or("tileheight", "8"),
+        tiles.join(","),
+        tileset.attr_or("tilewidth", "8")
+    ))
+}
+
+fn properties_json(node: &XmlNode) -> Vec<String> {
+    node.child("properties")
+        .map(|p| p.children_named("property").collect::<Vec<_>>())
+        .unwrap_or_default()
+        .iter()
+        .map(|p| {
+            // Multiline string values are stored as text instead
+            let value = p.attr("value").unwrap_or(&p.text);
+            format!(
+                r#"{{"name":{},"type":{},"value":{}}}"#,
+                json_string(p.attr_or("name", "")),
+                json_string(p.attr_or("type", "string")),
+                json_string(value)
+            )
+        })
+        .collect()
+}
+
+fn decode_base64(text: &str) -> Vec<u8> {
+    let value = |c: u8| match c {
+        b'A'..=b'Z' => c - b'A',
+        b'a'..=b'z' => c - b'a' + 26,
+        b'0'..=b'9' => c - b'0' + 52,
+        b'+' => 62,
+        _ => 63,
+    };
+    let chars: Vec<u8> = text
+        .bytes()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == b'+' || *c == b'/')
+        .map(value)
+        .collect();
+
+    let mut bytes = Vec::new();
+    for chunk in chars.chunks(4) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, c)| n | (*c as u32) << (18 - 6 * i));
+        bytes.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+    }
+
+    bytes
+}
@@ -0,0 +1,55 @@
+use escape::agent::{ActionSpace, AgentCommand};
+use escape::demo::Demo;
+use escape::editor::Editor;
+use escape::population::Population;
+use escape::*;
+
+mod common;
+
+fn max_move(population: &Population) -> u8 {
+    population
+        .games()
+        .iter()
+        .flat_map(|g| g.moves().iter().copied())
+        .max()
+        .unwrap()
+}
+
+#[test]
+fn population_keeps_its_action_space_across_generations() {
+    common::setup();
+    let editor = Editor::new();
+    let mut population = Population::with_action_space(0, 50, ActionSpace::EightWay);
+    assert_eq!(max_move(&population), 8);
+
+    (0..NUM_FRAMES).for_each(|frame| population.update(frame, &editor));
+    population.selection();
+    assert_eq!(population.action_space(), ActionSpace::EightWay);
+    assert!(population
+        .games()
+        .iter()
+        .all(|g| g.action_space == ActionSpace::EightWay));
+
+    let population = Population::with_action_space(0, 50, ActionSpace::FourWay);
+    assert_eq!(max_move(&population), 3);
+}
+
+#[test]
+fn demo_only_plays_moves_of_its_action_space() {
+    common::setup();
+    let mut demo = Demo::with_action_space(0, ActionSpace::FourWay);
+    assert!(!demo.play(AgentCommand::Stay));
+
+    let mut demo = Demo::with_action_space(0, ActionSpace::FourWayStay);
+    assert!(demo.play(AgentCommand::Stay));
+    assert!(!demo.play(AgentCommand::TopLeft));
+    assert!(demo.genome().iter().all(|m| *m < 5));
+}
+
+#[test]
+fn action_spaces_by_name() {
+    for space in ActionSpace::ALL {
+        assert_eq!(ActionSpace::from_name(space.name()), Some(space));
+    }
+    assert_eq!(ActionSpace::from_name("six"), None);
+}
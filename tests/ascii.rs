@@ -0,0 +1,65 @@
+use escape::agent::AgentCommand;
+use escape::ascii::AsciiGrid;
+use escape::game::Game;
+use escape::level::GameItem;
+use escape::*;
+
+mod common;
+
+const LEVEL: &str = "\
+#########################
+#.......................#
+#.......................#
+#.....................K.#
+#.......................#
+#.........#.............#
+#.........#........######
+#.........##########....#
+#..................#....D
+#.@................#....#
+#..................#....#
+#..................#....#
+#...........#...........#
+#...........#...........#
+#...........#...........#
+#...........#...........#
+#########################
+";
+
+#[test]
+fn level_snapshot() {
+    common::setup();
+    let lvl = &RESOURCES.get().unwrap().lvl_info;
+
+    assert_eq!(AsciiGrid::from_level(lvl).to_string(), LEVEL);
+}
+
+#[test]
+fn level_snapshot_draws_enemy_kinds() {
+    common::setup();
+    let mut lvl = RESOURCES.get().unwrap().lvl_info.clone();
+    let with_kind = |pos, kind: &str| {
+        let mut item = GameItem::new(pos, 77);
+        item.properties.insert("enemy".into(), kind.into());
+        item
+    };
+    lvl.enemies.push(with_kind((3, 1), "chaser"));
+    lvl.enemies.push(with_kind((4, 1), "turret"));
+    lvl.spikes.push(with_kind((5, 1), "turret"));
+    lvl.spikes.push(with_kind((6, 1), "timed_spike"));
+
+    let expected = LEVEL.replacen("#.......................#", "#..cTT^.................#", 1);
+    assert_eq!(AsciiGrid::from_level(&lvl).to_string(), expected);
+}
+
+#[test]
+fn game_snapshot() {
+    common::setup();
+    let mut game = Game::with_moves(&[AgentCommand::Right.to_int(); 3]);
+    assert_eq!(AsciiGrid::from_game(&game).to_string(), LEVEL);
+
+    // The agent moves, the rest of the room stays as is
+    (0..3).for_each(|frame| game.update(frame));
+    let moved = LEVEL.replace("#.@......", "#....@...");
+    assert_eq!(AsciiGrid::from_game(&game).to_string(), moved);
+}
@@ -0,0 +1,28 @@
+use std::fs;
+
+use escape::camera::MouseCam;
+use macroquad::prelude::*;
+
+#[test]
+fn broken_camera_states_are_ignored() {
+    let path = std::env::temp_dir().join(format!("escape_camera_{}.txt", std::process::id()));
+    let path = path.to_str().unwrap();
+    let load = |state: &str| {
+        fs::write(path, state).unwrap();
+        MouseCam::load(path, Vec2::ZERO)
+    };
+
+    assert!(load("0.25 0.04 0.005").is_some());
+    for state in [
+        "0.25 0.04 0",
+        "0.25 0.04 -0.005",
+        "0.25 0.04 NaN",
+        "inf 0.04 0.005",
+        "0.25 0.04",
+    ] {
+        assert!(load(state).is_none(), "{}", state);
+    }
+
+    fs::remove_file(path).unwrap();
+    assert!(MouseCam::load(path, Vec2::ZERO).is_none());
+}
@@ -0,0 +1,67 @@
+use escape::enemy::{is_crossing, CollisionKind, EnemyManager};
+use escape::level::GameItem;
+
+mod common;
+
+fn crab(pos: (usize, usize), speed: &str) -> GameItem {
+    let mut item = GameItem::new(pos, 77);
+    item.properties.insert("axis".into(), "horizontal".into());
+    item.properties
+        .insert("direction".into(), "positive".into());
+    item.properties.insert("speed".into(), speed.into());
+    item
+}
+
+#[test]
+fn swapping_tiles_with_a_crab_is_a_collision() {
+    common::setup();
+    let mut enemies = EnemyManager::new(vec![crab((10, 3), "1")], vec![]);
+
+    // Agent steps left onto the crab while the crab steps right onto the agent
+    let collision = enemies.update(&(11, 3), &(10, 3), 0);
+
+    let collision = collision.expect("agent and crab passed through each other");
+    assert_eq!(collision.kind, CollisionKind::Crab);
+    assert_eq!(collision.pos, (10, 3));
+}
+
+#[test]
+fn swapping_tiles_is_not_a_collision_without_swept_collision() {
+    common::setup();
+    let mut enemies = EnemyManager::new(vec![crab((10, 3), "1")], vec![]);
+    enemies.is_swept_collision = false;
+
+    // Only the end positions are compared, the old rule
+    assert!(enemies.update(&(11, 3), &(10, 3), 0).is_none());
+}
+
+#[test]
+fn fast_crab_walking_over_the_agent_is_a_collision() {
+    common::setup();
+    let mut enemies = EnemyManager::new(vec![crab((10, 3), "2")], vec![]);
+
+    // Crab moves two tiles, over the agent standing in between
+    let collision = enemies.update(&(11, 3), &(11, 3), 0);
+
+    assert!(collision.is_some());
+}
+
+#[test]
+fn moving_away_from_a_crab_is_not_a_collision() {
+    common::setup();
+    let mut enemies = EnemyManager::new(vec![crab((10, 3), "1")], vec![]);
+
+    assert!(enemies.update(&(12, 3), &(13, 3), 0).is_none());
+}
+
+#[test]
+fn crossing_checks() {
+    // Swap
+    assert!(is_crossing(&[(1, 1), (2, 1)], &(2, 1), &(1, 1)));
+    // Walked over
+    assert!(is_crossing(&[(1, 1), (2, 1), (3, 1)], &(2, 1), &(2, 1)));
+    // Following the agent
+    assert!(!is_crossing(&[(1, 1), (2, 1)], &(2, 1), &(3, 1)));
+    // Not moving
+    assert!(!is_crossing(&[(1, 1)], &(1, 2), &(1, 1)));
+}
@@ -0,0 +1,22 @@
+// Each test binary only uses some of the helpers
+#![allow(dead_code)]
+
+use std::sync::Once;
+
+use escape::resources::init_resources_headless;
+
+static INIT: Once = Once::new();
+
+/// Loads the resources once per test binary, loading them twice panics
+pub fn setup() {
+    INIT.call_once(init_resources_headless);
+}
+
+/// Like `setup`, running `f` once the resources are loaded to change the
+/// levels the tests run on
+pub fn setup_with(f: impl FnOnce()) {
+    INIT.call_once(|| {
+        init_resources_headless();
+        f();
+    });
+}
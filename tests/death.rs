@@ -0,0 +1,58 @@
+use escape::agent::AgentCommand;
+use escape::enemy::CollisionKind;
+use escape::game::Game;
+use escape::level::{self, LevelInfo};
+use escape::resources::{init_resources_headless, Resources};
+use escape::*;
+use macroquad::prelude::Texture2D;
+
+#[test]
+fn death_records_its_cause_and_costs_fitness() {
+    init_resources_headless();
+    // The main level has no spikes, swap in one that does
+    let lvl_map = level::load_map(
+        &std::fs::read_to_string("tiled/lvl3.json").unwrap(),
+        &[(TILE_SET_NAME, Texture2D::empty())],
+    )
+    .unwrap();
+    let lvl_info = LevelInfo::new(&lvl_map).unwrap();
+    RESOURCES.replace(Resources {
+        lvl_map,
+        lvl_background_sprite: Texture2D::empty(),
+        lvl_info,
+    });
+    let lvl = &RESOURCES.get().unwrap().lvl_info;
+
+    // Walk into a spike from a free tile next to it
+    let (start, spike, command) = lvl
+        .spikes
+        .iter()
+        .flat_map(|s| {
+            let (x, y) = s.pos;
+            [
+                ((x + 1, y), AgentCommand::Left),
+                ((x - 1, y), AgentCommand::Right),
+                ((x, y + 1), AgentCommand::Top),
+                ((x, y - 1), AgentCommand::Bottom),
+            ]
+            .map(|(start, command)| (start, s.pos, command))
+        })
+        .find(|(start, _, _)| !lvl.is_wall(*start) && lvl.spikes.iter().all(|s| s.pos != *start))
+        .expect("No spike to walk into");
+
+    let mut game = Game::with_layout(&[command.to_int()], start, lvl.key);
+    game.update(0);
+
+    assert!(game.is_dead);
+    let death = game.death.as_ref().unwrap();
+    assert_eq!(death.kind, CollisionKind::Spike);
+    assert_eq!(death.pos, spike);
+    assert_eq!(death.frame, 0);
+
+    let fitness = game.fitness_breakdown(&10, &20);
+    let progress = fitness.key_score + fitness.door_score + fitness.bonus;
+    assert!(fitness.death_penalty > 0.0);
+    assert!((fitness.death_penalty - progress * DEATH_PENALTY / 100.0).abs() < 1e-3);
+    assert!((fitness.total - (progress - fitness.death_penalty)).abs() < 1e-3);
+    assert_eq!(game.fitness(&10, &20), fitness.total);
+}
@@ -0,0 +1,135 @@
+use escape::editor::Editor;
+use escape::simulation::{Halt, StepTarget};
+use escape::*;
+
+mod common;
+
+/// Updates until the simulation halts, like the main loop does
+fn run(simulation: &mut Simulation, editor: &mut Editor) -> Halt {
+    for _ in 0..NUM_FRAMES * 20 {
+        simulation.update(editor);
+        if let Some(halt) = simulation.take_halt() {
+            editor.settings.is_pause = true;
+            if matches!(halt, Halt::Reached(_)) {
+                editor.settings.step_target = None;
+            }
+            return halt;
+        }
+    }
+    panic!("The simulation didn't halt");
+}
+
+#[test]
+fn step_targets_and_breakpoints() {
+    common::setup();
+    let mut editor = Editor::new();
+    let mut simulation = Simulation::new();
+
+    // Paused, nothing runs until a step is asked for
+    editor.settings.is_pause = true;
+    assert!(simulation.update(&editor).is_none());
+
+    editor.settings.run_until(StepTarget::Frame);
+    let stats = simulation.update(&editor).unwrap();
+    assert_eq!(stats.frame_count, 1);
+    assert!(simulation.update(&editor).is_none());
+    assert_eq!(
+        simulation.take_halt(),
+        Some(Halt::Reached(StepTarget::Frame))
+    );
+
+    // The episode stops on its last frame, before the selection
+    editor.settings.run_until(StepTarget::Episode);
+    assert_eq!(
+        run(&mut simulation, &mut editor),
+        Halt::Reached(StepTarget::Episode)
+    );
+    assert!(editor.settings.is_pause);
+    editor.settings.run_until(StepTarget::Frame);
+    let stats = simulation.update(&editor).unwrap();
+    assert_eq!((stats.generation_count, stats.frame_count), (2, 0));
+    simulation.take_halt();
+
+    editor.settings.run_until(StepTarget::Generation);
+    assert_eq!(
+        run(&mut simulation, &mut editor),
+        Halt::Reached(StepTarget::Generation)
+    );
+    editor.settings.run_until(StepTarget::UntilGeneration(5));
+    assert_eq!(
+        run(&mut simulation, &mut editor),
+        Halt::Reached(StepTarget::UntilGeneration(5))
+    );
+    let stats = simulation.step(&editor);
+    assert_eq!(stats.generation_count, 5);
+
+    // Breakpoints pause without dropping the target
+    editor.settings.is_break_on_key = true;
+    editor.settings.is_break_on_best_fitness = true;
+    editor.settings.run_until(StepTarget::UntilGeneration(50));
+    match run(&mut simulation, &mut editor) {
+        Halt::KeyCollected => assert!(simulation
+            .population()
+            .games()
+            .iter()
+            .any(|g| g.is_key_collected)),
+        Halt::BestFitness(fitness) => assert!(fitness > 0.0),
+        halt => panic!("Unexpected halt {:?}", halt),
+    }
+    assert_eq!(
+        editor.settings.step_target,
+        Some(StepTarget::UntilGeneration(50))
+    );
+}
+
+#[test]
+fn key_breakpoint_pauses_once() {
+    common::setup();
+    let mut editor = Editor::new();
+    let mut simulation = Simulation::new();
+
+    editor.settings.is_break_on_key = true;
+    editor.settings.run_until(StepTarget::UntilGeneration(30));
+    let generation = match run(&mut simulation, &mut editor) {
+        Halt::KeyCollected => simulation.step(&editor).generation_count,
+        // No agent got the key, nothing to pause on again
+        Halt::Reached(_) => return,
+        halt => panic!("Unexpected halt {:?}", halt),
+    };
+
+    // Later generations get the key too, without pausing again
+    editor
+        .settings
+        .run_until(StepTarget::UntilGeneration(generation + 3));
+    assert_eq!(
+        run(&mut simulation, &mut editor),
+        Halt::Reached(StepTarget::UntilGeneration(generation + 3))
+    );
+}
+
+#[test]
+fn only_frames_step_without_the_ai() {
+    common::setup();
+    let mut editor = Editor::new();
+    let mut simulation = Simulation::new();
+    editor.settings.is_pause = true;
+    editor.settings.is_ai_enabled = false;
+
+    // The episode would never end
+    for target in [
+        StepTarget::Episode,
+        StepTarget::Generation,
+        StepTarget::Solved,
+        StepTarget::UntilGeneration(3),
+    ] {
+        editor.settings.run_until(target);
+        assert_eq!(editor.settings.step_target, None);
+        assert!(editor.settings.is_pause);
+    }
+
+    editor.settings.run_until(StepTarget::Frame);
+    assert_eq!(
+        run(&mut simulation, &mut editor),
+        Halt::Reached(StepTarget::Frame)
+    );
+}
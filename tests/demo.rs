@@ -0,0 +1,74 @@
+use std::fs;
+
+use escape::agent::AgentCommand;
+use escape::demo::Demo;
+use escape::editor::Editor;
+use escape::game::Game;
+use escape::population::Population;
+use escape::resources::init_resources_headless;
+use escape::*;
+
+#[test]
+fn demos_are_recorded_and_seed_the_next_generation() {
+    init_resources_headless();
+
+    // Every move played is kept, in order
+    let mut demo = Demo::on_level(0);
+    let played = [
+        AgentCommand::Right,
+        AgentCommand::Bottom,
+        AgentCommand::Left,
+        AgentCommand::Top,
+    ];
+    let recorded: Vec<u8> = played.iter().map(|c| c.to_int()).collect();
+    for command in played {
+        assert!(demo.play(command));
+    }
+    let is_diagonal_allowed = ACTION_SPACE.num_actions() > AgentCommand::TopLeft.to_int();
+    assert_eq!(demo.play(AgentCommand::TopLeft), is_diagonal_allowed);
+    let num_played = demo.frame_count();
+    assert_eq!(&demo.game().moves()[..4], &recorded[..]);
+
+    let genome = demo.genome();
+    assert_eq!(genome.len(), NUM_FRAMES);
+    assert_eq!(&genome[..num_played], demo.game().moves());
+
+    // Saved the way the play and eval tools read genomes
+    let dir = std::env::temp_dir().join(format!("escape_demos_{}", std::process::id()));
+    let dir = dir.to_str().unwrap();
+    let path = demo.save(dir).unwrap();
+    // The moves after the played ones are drawn again on each save
+    let saved = Game::parse_moves(&fs::read_to_string(&path).unwrap());
+    assert_eq!(saved.len(), NUM_FRAMES);
+    assert_eq!(&saved[..num_played], demo.game().moves());
+    // A file where the directory should be
+    assert!(demo.save(&path).is_err());
+    fs::remove_dir_all(dir).unwrap();
+
+    // The demo goes in once as is, and mutated for a share of the children
+    let editor = Editor::new();
+    let mut population = Population::on_level(0, 200);
+    population.add_demo(genome.clone());
+    assert_eq!(population.num_queued_demos(), 1);
+    (0..NUM_FRAMES).for_each(|frame| population.update(frame, &editor));
+    population.selection();
+    assert_eq!(population.num_queued_demos(), 0);
+    assert_eq!(population.games().len(), 200);
+
+    let games = population.games();
+    assert!(games.iter().any(|g| g.moves() == &genome[..]));
+    let num_close = games
+        .iter()
+        .filter(|g| {
+            let num_diff = g
+                .moves()
+                .iter()
+                .zip(&genome)
+                .filter(|(a, b)| a != b)
+                .count();
+            num_diff < NUM_FRAMES / 10
+        })
+        .count();
+    let num_children = 200 - (200.0 * (POP_RETENTION_RATE + POP_EXPO_PERCENTAGE) / 100.0) as usize;
+    assert!(num_close >= (num_children as f32 * DEMO_CHILDREN_PERCENTAGE / 100.0) as usize);
+}
@@ -0,0 +1,92 @@
+use escape::enemy::{CollisionKind, EnemyManager};
+use escape::level::GameItem;
+use escape::*;
+
+mod common;
+
+fn enemy(pos: (usize, usize), kind: &str, properties: &[(&str, &str)]) -> GameItem {
+    let mut item = GameItem::new(pos, 77);
+    item.properties.insert("enemy".into(), kind.into());
+    for (name, value) in properties {
+        item.properties.insert(name.to_string(), value.to_string());
+    }
+    item
+}
+
+#[test]
+fn chaser_walks_around_walls_to_the_agent() {
+    common::setup();
+    let lvl = &RESOURCES.get().unwrap().lvl_info;
+    let chaser = enemy((11, 6), "chaser", &[("speed", "1")]);
+    let mut enemies = EnemyManager::new(vec![chaser], vec![]);
+    let agent = (11, 8);
+
+    // The wall below the chaser is in the way, the way round on the left is
+    // ten tiles long
+    let mut pos = (11, 6);
+    for frame in 0..9 {
+        assert!(enemies.update(&agent, &agent, frame).is_none());
+        let next = enemies.positions()[0].0;
+        assert_eq!(next.0.abs_diff(pos.0) + next.1.abs_diff(pos.1), 1);
+        assert!(!lvl.is_wall(next));
+        pos = next;
+    }
+    let collision = enemies
+        .update(&agent, &agent, 9)
+        .expect("chaser reached the agent");
+    assert_eq!(collision.kind, CollisionKind::Chaser);
+    assert_eq!(collision.pos, agent);
+}
+
+#[test]
+fn timed_spike_is_only_deadly_while_extended() {
+    common::setup();
+    let spike = enemy((5, 3), "timed_spike", &[("period", "4"), ("active", "2")]);
+    let mut enemies = EnemyManager::new(vec![], vec![spike]);
+
+    let is_extended: Vec<bool> = (0..10)
+        .map(|frame| {
+            enemies.update(&(1, 1), &(1, 1), frame);
+            enemies.positions()[0].2
+        })
+        .collect();
+    assert_eq!(
+        is_extended,
+        [true, false, false, true, true, false, false, true, true, false]
+    );
+
+    // Standing on it is fine until it extends again
+    let collision = (10..20).find_map(|frame| enemies.update(&(5, 3), &(5, 3), frame));
+    let collision = collision.expect("spike extended under the agent");
+    assert_eq!(collision.kind, CollisionKind::Spike);
+    assert_eq!(collision.frame, 11);
+}
+
+#[test]
+fn turret_projectiles_fly_straight_and_hit() {
+    common::setup();
+    let turret = enemy((2, 2), "turret", &[("direction", "right"), ("period", "5")]);
+    let mut enemies = EnemyManager::new(vec![], vec![turret]);
+
+    // Fired on the first frame next to the turret, then one tile per frame
+    let collision = (0..10).find_map(|frame| enemies.update(&(8, 2), &(8, 2), frame));
+    let collision = collision.expect("projectile reached the agent");
+    assert_eq!(collision.kind, CollisionKind::Projectile);
+    assert_eq!(collision.pos, (8, 2));
+    assert_eq!(collision.frame, 5);
+}
+
+#[test]
+fn swapping_tiles_with_a_projectile_is_a_collision() {
+    common::setup();
+    let turret = enemy((2, 2), "turret", &[("direction", "right"), ("period", "5")]);
+    let mut enemies = EnemyManager::new(vec![], vec![turret]);
+    enemies.update(&(9, 9), &(9, 9), 0);
+    enemies.update(&(9, 9), &(9, 9), 1);
+
+    // The projectile moves from 4 to 5 as the agent moves from 5 to 4
+    let collision = enemies.update(&(5, 2), &(4, 2), 2);
+
+    let collision = collision.expect("agent and projectile passed through each other");
+    assert_eq!(collision.kind, CollisionKind::Projectile);
+}
@@ -0,0 +1,135 @@
+use std::fs;
+
+use escape::export::TiledLevel;
+use escape::level::LevelInfo;
+use escape::TILE_SET_NAME;
+use macroquad::prelude::Texture2D;
+use macroquad_tiled::Map;
+
+fn load(json: &str) -> Map {
+    macroquad_tiled::load_map(json, &[(TILE_SET_NAME, Texture2D::empty())], &[]).unwrap()
+}
+
+fn shipped_levels() -> Vec<String> {
+    let mut paths: Vec<String> = fs::read_dir("tiled")
+        .unwrap()
+        .map(|e| e.unwrap().path().to_string_lossy().to_string())
+        .filter(|p| p.ends_with(".json"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    paths
+}
+
+#[test]
+fn shipped_levels_round_trip() {
+    for path in shipped_levels() {
+        let original = load(&fs::read_to_string(&path).unwrap());
+        let level = TiledLevel::from_map(&original);
+        let written = load(&level.to_json());
+
+        // Same layers, tiles and tile properties
+        let rewritten = TiledLevel::from_map(&written);
+        assert_eq!(level.size, rewritten.size, "{}", path);
+        assert_eq!(level.layers, rewritten.layers, "{}", path);
+        for (a, b) in level.tilesets.iter().zip(rewritten.tilesets.iter()) {
+            assert_eq!(a.name, b.name, "{}", path);
+            assert_eq!(a.first_gid, b.first_gid, "{}", path);
+            assert_eq!(a.columns, b.columns, "{}", path);
+            let properties = |t: &escape::export::Tileset| {
+                t.tile_properties
+                    .iter()
+                    .map(|(id, p)| {
+                        let p: Vec<_> = p.iter().map(|p| (&p.name, &p.ty, &p.value)).collect();
+                        format!("{} {:?}", id, p)
+                    })
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(properties(a), properties(b), "{}", path);
+        }
+
+        // And the same level once parsed
+        let (a, b) = (
+            LevelInfo::new(&original).unwrap(),
+            LevelInfo::new(&written).unwrap(),
+        );
+        assert_eq!(a.size, b.size, "{}", path);
+        assert_eq!(a.key, b.key, "{}", path);
+        assert_eq!(a.door, b.door, "{}", path);
+        assert_eq!(a.agent, b.agent, "{}", path);
+        assert_eq!(a.walls, b.walls, "{}", path);
+        for (items_a, items_b) in [(&a.spikes, &b.spikes), (&a.enemies, &b.enemies)] {
+            assert_eq!(items_a.len(), items_b.len(), "{}", path);
+            for (x, y) in items_a.iter().zip(items_b.iter()) {
+                assert_eq!((x.pos, x.value), (y.pos, y.value), "{}", path);
+                assert_eq!(x.properties, y.properties, "{}", path);
+            }
+        }
+    }
+}
+
+#[test]
+fn shipped_layout_is_written() {
+    let json = fs::read_to_string("tiled/lvl5.json").unwrap();
+    let written = TiledLevel::from_map(&load(&json)).to_json();
+    let map = load(&written);
+    for layer in [
+        "background",
+        "walls",
+        "door",
+        "keys",
+        "player",
+        "spikes",
+        "enemies",
+    ] {
+        assert!(map.contains_layer(layer), "Missing layer {}", layer);
+    }
+    assert_eq!(map.raw_tiled_map.tilesets[0].image, "tileset.png");
+}
+
+#[test]
+fn object_layers_are_written() {
+    // Walls around a 6x5 room, everything else placed as objects
+    let walls: Vec<String> = (0..30)
+        .map(|i| match (i % 6, i / 6) {
+            (0 | 5, _) | (_, 0 | 4) => "1".to_string(),
+            _ => "0".to_string(),
+        })
+        .collect();
+    let json = format!(
+        r#"{{"height":5,"width":6,"tilewidth":8,"tileheight":8,"orientation":"orthogonal","renderorder":"right-down","type":"map",
+"layers":[{{"data":[{}],"height":5,"width":6,"name":"walls","opacity":1,"type":"tilelayer","visible":true,"x":0,"y":0}},
+{{"name":"things","opacity":1,"type":"objectgroup","visible":true,"x":0,"y":0,"objects":[
+ {{"id":1,"name":"player","type":"","x":8,"y":8,"width":0,"height":0,"rotation":0,"visible":true}},
+ {{"id":2,"name":"","type":"key","x":32,"y":8,"width":0,"height":0,"rotation":0,"visible":true}},
+ {{"id":3,"name":"door","type":"","x":40,"y":24,"width":8,"height":8,"rotation":0,"visible":true}},
+ {{"id":4,"name":"","type":"crab","gid":78,"x":8,"y":24,"width":8,"height":8,"rotation":0,"visible":true,
+   "properties":[{{"name":"path","type":"string","value":"1,2;4,2"}},{{"name":"note","type":"string","value":"tab\tand\u0001"}}]}}
+]}}],
+"tilesets":[{{"columns":12,"firstgid":1,"image":"{}","imageheight":100,"imagewidth":100,"margin":0,"name":"tileset","spacing":0,"tilecount":144,"tileheight":8,"tilewidth":8}}]}}"#,
+        walls.join(","),
+        TILE_SET_NAME
+    );
+
+    let original = load(&json);
+    let level = TiledLevel::from_map(&original);
+    assert_eq!(level.object_layers.len(), 1);
+    let written = load(&level.to_json());
+    let rewritten = TiledLevel::from_map(&written);
+    assert_eq!(rewritten.object_layers[0].objects.len(), 4);
+    let crab = &rewritten.object_layers[0].objects[3];
+    assert_eq!(crab.gid, Some(78));
+    assert_eq!(crab.properties[1].value, "tab\tand\u{1}");
+
+    let (a, b) = (
+        LevelInfo::new(&original).unwrap(),
+        LevelInfo::new(&written).unwrap(),
+    );
+    assert_eq!((a.agent, a.key, a.door), ((1, 1), (4, 1), (5, 3)));
+    assert_eq!((a.agent, a.key, a.door), (b.agent, b.key, b.door));
+    assert_eq!(b.enemies.len(), 1);
+    assert_eq!(a.enemies[0].pos, b.enemies[0].pos);
+    assert_eq!(a.enemies[0].properties, b.enemies[0].properties);
+    assert_eq!(b.enemies[0].property("path"), Some("1,2;4,2"));
+}
@@ -0,0 +1,102 @@
+use std::fs;
+
+use escape::generator::{generate, save_room, Algorithm, GeneratorConfig, Room};
+use escape::level::{self, LevelInfo};
+use escape::TILE_SET_NAME;
+use macroquad::prelude::Texture2D;
+
+#[test]
+fn generated_rooms_are_solvable() {
+    for algorithm in [Algorithm::Rooms, Algorithm::Maze] {
+        for seed in 0..20 {
+            let config = GeneratorConfig {
+                size: (31, 21),
+                difficulty: seed as f32 / 20.0,
+                seed,
+                algorithm,
+            };
+            let room = generate(&config);
+            assert!(
+                room.is_solvable(),
+                "{:?} seed {} is unsolvable",
+                algorithm,
+                seed
+            );
+            assert!(!room.is_wall(room.agent) && !room.is_wall(room.key));
+            assert!(!room.spikes.contains(&room.agent) && !room.spikes.contains(&room.key));
+        }
+    }
+}
+
+#[test]
+fn mazes_have_crab_patrols() {
+    for seed in 0..10 {
+        let room = generate(&GeneratorConfig {
+            size: (31, 21),
+            difficulty: 1.0,
+            seed,
+            algorithm: Algorithm::Maze,
+        });
+        assert!(!room.crabs.is_empty(), "Maze seed {} has no crabs", seed);
+
+        // Every crab has room to walk along its axis
+        for crab in &room.crabs {
+            let (x, y) = crab.pos;
+            let along = match crab.is_vertical {
+                true => [(x, y - 1), (x, y + 1)],
+                false => [(x - 1, y), (x + 1, y)],
+            };
+            assert!(along.iter().any(|pos| !room.is_wall(*pos)));
+        }
+    }
+}
+
+#[test]
+fn same_seed_gives_the_same_room() {
+    let config = GeneratorConfig {
+        seed: 42,
+        ..Default::default()
+    };
+    let (a, b) = (generate(&config), generate(&config));
+    assert_eq!(a.to_tiled().to_json(), b.to_tiled().to_json());
+
+    let other = generate(&GeneratorConfig {
+        seed: 43,
+        ..Default::default()
+    });
+    assert_ne!(a.to_tiled().to_json(), other.to_tiled().to_json());
+}
+
+#[test]
+fn saving_a_room_reports_errors() {
+    let room = generate(&GeneratorConfig::default());
+    let dir = std::env::temp_dir().join(format!("escape_save_room_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let out_path = dir.join("room").to_string_lossy().to_string();
+
+    assert!(save_room(&room, &out_path).is_ok());
+    assert!(dir.join("room.json").exists());
+    assert!(dir.join("room.png").exists());
+
+    let missing = dir.join("missing/room").to_string_lossy().to_string();
+    assert!(save_room(&room, &missing).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+fn level_info(path: &str) -> LevelInfo {
+    let data = fs::read_to_string(path).unwrap();
+    let map = level::load_map(&data, &[(TILE_SET_NAME, Texture2D::empty())]).unwrap();
+    LevelInfo::new(&map).unwrap()
+}
+
+#[test]
+fn editing_refuses_levels_a_room_cant_hold() {
+    for lvl in ["tiled/lvl2.json", "tiled/lvl4.json"] {
+        assert!(Room::from_level(&level_info(lvl)).is_ok(), "{}", lvl);
+    }
+    // Both have large spikes, which would come back small
+    for lvl in ["tiled/lvl3.json", "tiled/lvl5.json"] {
+        let error = Room::from_level(&level_info(lvl)).err().unwrap();
+        assert!(error.contains("large or hidden spikes"), "{}", error);
+    }
+}
@@ -0,0 +1,68 @@
+use escape::input::{parse_key, Action, InputMap};
+use escape::KEY_BINDINGS;
+use macroquad::prelude::KeyCode;
+
+#[test]
+fn default_bindings() {
+    let map = InputMap::new();
+    for action in Action::ALL {
+        assert!(!map.keys(action).is_empty(), "{:?} has no key", action);
+        assert_eq!(Action::from_name(action.name()), Some(action));
+    }
+
+    // A key does one thing only
+    let mut keys: Vec<KeyCode> = KEY_BINDINGS.iter().flat_map(|(_, k)| k.to_vec()).collect();
+    let num_keys = keys.len();
+    keys.sort_by_key(|k| format!("{:?}", k));
+    keys.dedup();
+    assert_eq!(keys.len(), num_keys);
+}
+
+#[test]
+fn bindings_file_overrides_defaults() {
+    let map = InputMap::parse(
+        "# Controls for a left handed layout\n\
+         pause = P, Kp0\n\
+         move_up = up # arrows instead of wasd\n\
+         restart = NotAKey, F5\n\
+         fly = Space\n",
+    );
+    assert_eq!(map.keys(Action::Pause), &[KeyCode::P, KeyCode::Kp0]);
+    assert_eq!(map.keys(Action::MoveUp), &[KeyCode::Up]);
+    assert_eq!(map.keys(Action::Restart), &[KeyCode::F5]);
+    assert_eq!(map.keys(Action::ToggleUi), &[KeyCode::Tab]);
+    assert_eq!(map.keys_text(Action::Pause), "P, Kp0");
+
+    // Saved bindings read back the same
+    assert_eq!(InputMap::parse(&map.to_text()), map);
+    assert_eq!(parse_key("backslash"), Some(KeyCode::Backslash));
+    assert_eq!(parse_key("Key7"), Some(KeyCode::Key7));
+}
+
+#[test]
+fn binding_adds_keys_without_stealing_them() {
+    let mut map = InputMap::new();
+    let pause = map.keys(Action::Pause).to_vec();
+
+    assert_eq!(map.bind(Action::Pause, KeyCode::F9), Ok(()));
+    assert_eq!(
+        map.keys(Action::Pause),
+        &[pause.clone(), vec![KeyCode::F9]].concat()[..]
+    );
+    // Binding it again changes nothing
+    assert_eq!(map.bind(Action::Pause, KeyCode::F9), Ok(()));
+    assert_eq!(map.keys(Action::Pause).len(), pause.len() + 1);
+
+    // Keys of other actions are refused, and stay with them
+    let move_up = map.keys(Action::MoveUp)[0];
+    assert_eq!(map.bind(Action::Pause, move_up), Err(Action::MoveUp));
+    assert_eq!(map.action_of(move_up), Some(Action::MoveUp));
+    assert!(!map.keys(Action::Pause).contains(&move_up));
+
+    map.set_keys(Action::Pause, Vec::new());
+    assert!(map.keys(Action::Pause).is_empty());
+    assert_eq!(map.action_of(KeyCode::F9), None);
+
+    // Saving somewhere unwritable only logs it
+    map.save("/nonexistent/dir/input.txt");
+}
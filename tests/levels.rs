@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use escape::editor::Editor;
+use escape::generator::{generate, GeneratorConfig};
+use escape::level::{self, LevelInfo};
+use escape::level_editor::LevelEditor;
+use escape::resources::Resources;
+use escape::simulation::split_games;
+use escape::*;
+use macroquad::prelude::Texture2D;
+
+mod common;
+
+/// Loads the main level and a large room as the second, returns its size
+fn setup() -> (usize, usize) {
+    common::setup_with(|| RESOURCES.push(large_room()));
+    RESOURCES.level(1).unwrap().lvl_info.size
+}
+
+/// A generated room, much larger than the shipped levels
+fn large_room() -> Resources {
+    let room = generate(&GeneratorConfig {
+        size: (61, 41),
+        seed: 7,
+        ..Default::default()
+    });
+    let lvl_map = level::load_map(
+        &room.to_tiled().to_json(),
+        &[(TILE_SET_NAME, Texture2D::empty())],
+    )
+    .unwrap();
+    let lvl_info = LevelInfo::new(&lvl_map).unwrap();
+    Resources {
+        lvl_map,
+        lvl_background_sprite: Texture2D::empty(),
+        lvl_info,
+    }
+}
+
+#[test]
+fn levels_of_different_sizes_train_together() {
+    let size = setup();
+    assert_eq!(RESOURCES.num_levels(), 2);
+
+    let editor = Editor::new();
+    let mut simulation = Simulation::new();
+    let mut stats = simulation.step(&editor);
+    while stats.generation_count <= 2 {
+        stats = simulation.step(&editor);
+    }
+
+    // Games are split between the levels and stay on theirs over generations
+    let populations = simulation.populations();
+    assert_eq!(populations.len(), 2);
+    assert_eq!(
+        populations[0].games().len() + populations[1].games().len(),
+        NUM_GAMES as usize
+    );
+    let main_size = RESOURCES.get().unwrap().lvl_info.size;
+    assert!(populations[0]
+        .games()
+        .iter()
+        .all(|g| g.lvl.size == main_size));
+    assert!(populations[1]
+        .games()
+        .iter()
+        .all(|g| g.level == 1 && g.lvl.size == size));
+
+    // The multi view puts the large rooms in a block below the main level
+    let main_block = (0..populations[0].games().len())
+        .map(|i| populations[0].game_rect(i))
+        .fold(0.0f32, |bottom, r| bottom.max(r.bottom()));
+    for i in 0..populations[1].games().len() {
+        let rect = populations[1].game_rect(i);
+        assert!(rect.y > main_block);
+        assert_eq!(
+            rect.w as usize,
+            size.0 * (UNIT_FRAME_SIZE * FRAME_SCALE) as usize
+        );
+    }
+}
+
+#[test]
+fn games_are_split_without_dropping_any() {
+    for num_levels in 1..=12 {
+        let num_games = split_games(NUM_GAMES as usize, num_levels);
+        assert_eq!(num_games.len(), num_levels);
+        assert_eq!(num_games.iter().sum::<usize>(), NUM_GAMES as usize);
+        let (min, max) = (num_games.iter().min(), num_games.iter().max());
+        assert!(max.unwrap() - min.unwrap() <= 1, "{:?}", num_games);
+    }
+    assert_eq!(
+        split_games(1020, 7),
+        vec![146, 146, 146, 146, 146, 145, 145]
+    );
+    // More levels than games still plays every level
+    assert_eq!(split_games(2, 3), vec![1, 1, 1]);
+}
+
+#[test]
+fn level_editor_opens_any_level() {
+    let size = setup();
+
+    let level_editor = LevelEditor::new(1).unwrap();
+    assert_eq!(level_editor.level, 1);
+    assert_eq!(level_editor.room.size, size);
+    let main_size = RESOURCES.get().unwrap().lvl_info.size;
+    assert_eq!(LevelEditor::new(0).unwrap().room.size, main_size);
+
+    // Replacing the second level leaves the main one alone
+    let (main, second) = (RESOURCES.get().unwrap(), RESOURCES.level(1).unwrap());
+    RESOURCES.replace_level(1, large_room());
+    assert!(Arc::ptr_eq(&main, &RESOURCES.get().unwrap()));
+    assert!(!Arc::ptr_eq(&second, &RESOURCES.level(1).unwrap()));
+    assert_eq!(RESOURCES.num_levels(), 2);
+}
@@ -0,0 +1,51 @@
+use escape::game::Game;
+use escape::level::{self, LevelInfo};
+use escape::resources::{init_resources_headless, Resources};
+use escape::*;
+use macroquad::prelude::Texture2D;
+
+type Frame = ((usize, usize), Vec<((usize, usize), bool)>);
+
+fn play(game: &mut Game, num_frames: usize) -> Vec<Frame> {
+    (0..num_frames)
+        .map(|frame| {
+            game.update(frame);
+            let enemies = game
+                .enemy_positions()
+                .iter()
+                .map(|(pos, _, is_active)| (*pos, *is_active))
+                .collect();
+            (*game.get_current_agent_pos(), enemies)
+        })
+        .collect()
+}
+
+#[test]
+fn restarted_games_replay_the_same_episode() {
+    init_resources_headless();
+    // lvl4 has crabs
+    let lvl_map = level::load_map(
+        &std::fs::read_to_string("tiled/lvl4.json").unwrap(),
+        &[(TILE_SET_NAME, Texture2D::empty())],
+    )
+    .unwrap();
+    let lvl_info = LevelInfo::new(&lvl_map).unwrap();
+    RESOURCES.replace(Resources {
+        lvl_map,
+        lvl_background_sprite: Texture2D::empty(),
+        lvl_info,
+    });
+
+    // A layout other than the level's, like a random spawn and key draw
+    let lvl = &RESOURCES.get().unwrap().lvl_info;
+    let moves = Game::new().moves().to_vec();
+    let mut game = Game::with_layout(&moves, lvl.key, lvl.agent);
+    let episode = play(&mut game, 40);
+    assert!(!game.enemy_positions().is_empty());
+
+    let mut replay = game.restart();
+    assert_eq!(replay.lvl.agent, lvl.key);
+    assert_eq!(replay.lvl.key, lvl.agent);
+    assert!(!replay.is_dead && !replay.is_key_collected);
+    assert_eq!(play(&mut replay, 40), episode);
+}
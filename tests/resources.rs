@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use escape::game::Game;
+use escape::level::{self, LevelInfo};
+use escape::resources::{init_resources_headless, Resources};
+use escape::*;
+use macroquad::prelude::Texture2D;
+
+fn load(path: &str) -> Resources {
+    let lvl_map = level::load_map(
+        &std::fs::read_to_string(path).unwrap(),
+        &[(TILE_SET_NAME, Texture2D::empty())],
+    )
+    .unwrap();
+    let lvl_info = LevelInfo::new(&lvl_map).unwrap();
+
+    Resources {
+        lvl_map,
+        lvl_background_sprite: Texture2D::empty(),
+        lvl_info,
+    }
+}
+
+#[test]
+fn replaced_level_is_dropped_with_its_last_game() {
+    init_resources_headless();
+    let old = Arc::downgrade(&RESOURCES.get().unwrap());
+    let game = Game::new();
+
+    // Games of the old level keep playing on it
+    RESOURCES.replace(load("tiled/lvl3.json"));
+    assert!(old.upgrade().is_some());
+    let new_game = Game::new();
+    assert_ne!(new_game.lvl.walls, game.lvl.walls);
+
+    drop(game);
+    assert!(old.upgrade().is_none());
+}
@@ -0,0 +1,72 @@
+use std::cell::Cell;
+use std::time::Duration;
+
+use escape::scheduler::Scheduler;
+
+// 8 frames a second
+const FRAME: Duration = Duration::from_millis(125);
+
+#[test]
+fn runs_at_the_target_rate() {
+    let mut scheduler = Scheduler::new(Duration::from_secs(1));
+    let mut num_steps = 0;
+    let steps: Vec<usize> = (0..8)
+        .map(|_| {
+            scheduler.run_for(FRAME, 20.0, || {
+                num_steps += 1;
+                true
+            })
+        })
+        .collect();
+
+    // 2.5 steps a frame, the half step carries over
+    assert_eq!(steps, vec![2, 3, 2, 3, 2, 3, 2, 3]);
+    assert_eq!(num_steps, 20);
+    assert_eq!(scheduler.measured_rate(), 20.0);
+}
+
+#[test]
+fn steps_that_dont_run_are_dropped() {
+    let mut scheduler = Scheduler::new(Duration::from_secs(1));
+
+    // Paused after 3 of the 10 steps due
+    let mut num_left = 3;
+    let num_steps = scheduler.run_for(FRAME, 80.0, || {
+        num_left -= 1;
+        num_left >= 0
+    });
+    assert_eq!(num_steps, 3);
+    // The 7 that didn't run aren't owed
+    assert_eq!(scheduler.run_for(FRAME, 80.0, || true), 10);
+
+    // Nor counted in the rate
+    for _ in 0..6 {
+        scheduler.run_for(FRAME, 80.0, || false);
+    }
+    assert_eq!(scheduler.measured_rate(), 13.0);
+}
+
+#[test]
+fn stays_within_the_time_budget() {
+    let budget = Duration::from_millis(5);
+    let mut scheduler = Scheduler::new(budget);
+    // Each step takes a millisecond
+    let clock = Cell::new(Duration::ZERO);
+    for rate in [f32::INFINITY, 1_000_000.0] {
+        clock.set(Duration::ZERO);
+        let num_steps = scheduler.run_timed(
+            FRAME,
+            rate,
+            || clock.get(),
+            || {
+                clock.set(clock.get() + Duration::from_millis(1));
+                true
+            },
+        );
+        assert_eq!(num_steps, 5);
+    }
+
+    // Steps that didn't fit are dropped instead of piling up
+    clock.set(Duration::ZERO);
+    assert_eq!(scheduler.run_timed(FRAME, 1.0, || clock.get(), || true), 0);
+}
@@ -0,0 +1,68 @@
+use escape::agent::AgentCommand;
+use escape::game::Game;
+use escape::level::{self, LevelInfo};
+use escape::resources::Resources;
+use escape::*;
+use macroquad::prelude::Texture2D;
+
+mod common;
+
+/// The main level with an ice strip on the spawn row, from x 5 to 8
+fn setup() {
+    common::setup_with(|| {
+        let lvl_map = level::load_map(
+            &std::fs::read_to_string(LVL_MAP_PATH).unwrap(),
+            &[(TILE_SET_NAME, Texture2D::empty())],
+        )
+        .unwrap();
+        let mut lvl_info = LevelInfo::new(&lvl_map).unwrap();
+        for x in 5..=8 {
+            lvl_info.ice[9 * lvl_info.size.0 + x] = true;
+        }
+        RESOURCES.replace(Resources {
+            lvl_map,
+            lvl_background_sprite: Texture2D::empty(),
+            lvl_info,
+        });
+    });
+}
+
+#[test]
+fn agent_slides_over_ice() {
+    setup();
+    let moves: Vec<u8> = (0..8)
+        .map(|frame| match frame < 3 {
+            true => AgentCommand::Right.to_int(),
+            false => AgentCommand::Left.to_int(),
+        })
+        .collect();
+    let mut game = Game::with_layout(&moves, (2, 9), (22, 3));
+    game.action_noise = 0.0;
+
+    // Three steps onto the ice, then the left moves are ignored until the
+    // agent slides off the far side
+    let mut xs = Vec::new();
+    for frame in 0..moves.len() {
+        game.update(frame);
+        xs.push(game.get_current_agent_pos().0);
+    }
+    assert_eq!(xs, [3, 4, 5, 6, 7, 8, 9, 8]);
+    assert_eq!(game.get_current_agent_pos().1, 9);
+}
+
+#[test]
+fn action_noise_replaces_moves() {
+    setup();
+    let moves = vec![AgentCommand::Top.to_int(); 6];
+    let play = |noise: f32| {
+        let mut game = Game::with_layout(&moves, (2, 12), (22, 3));
+        game.action_noise = noise;
+        (0..moves.len()).for_each(|frame| game.update(frame));
+        *game.get_current_agent_pos()
+    };
+
+    // Without noise the moves are played as is, with only noise the agent
+    // wanders off
+    assert!((0..10).all(|_| play(0.0) == (2, 6)));
+    assert!((0..10).any(|_| play(100.0) != (2, 6)));
+}
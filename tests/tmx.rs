@@ -0,0 +1,278 @@
+use std::fs;
+
+use escape::export::TiledLevel;
+use escape::level::{self, LevelInfo};
+use escape::TILE_SET_NAME;
+use macroquad::prelude::Texture2D;
+use macroquad_tiled::Map;
+
+fn load(data: &str) -> Map {
+    level::load_map(data, &[(TILE_SET_NAME, Texture2D::empty())]).unwrap()
+}
+
+/// A level written the way Tiled saves `.tmx` maps, with csv layers
+fn to_tmx(level: &TiledLevel) -> String {
+    let (w, h) = level.size;
+    let tilesets: String = level
+        .tilesets
+        .iter()
+        .map(|t| {
+            let tiles: String = t
+                .tile_properties
+                .iter()
+                .map(|(id, properties)| {
+                    let properties: String = properties
+                        .iter()
+                        .map(|p| format!(r#"<property name="{}" value="{}"/>"#, p.name, p.value))
+                        .collect();
+                    format!(r#"<tile id="{}"><properties>{}</properties></tile>"#, id, properties)
+                })
+                .collect();
+            format!(
+                r#"<tileset firstgid="{}" name="{}" tilewidth="{}" tileheight="{}" tilecount="{}" columns="{}">
+  <image source="{}" width="{}" height="{}"/>
+  {}
+ </tileset>"#,
+                t.first_gid,
+                t.name,
+                t.tile_size.0,
+                t.tile_size.1,
+                t.tile_count,
+                t.columns,
+                t.image,
+                t.image_size.0,
+                t.image_size.1,
+                tiles
+            )
+        })
+        .collect();
+    let layers: String = level
+        .layers
+        .iter()
+        .enumerate()
+        .map(|(i, l)| {
+            let rows: Vec<String> = l
+                .data
+                .chunks(w)
+                .map(|row| {
+                    row.iter()
+                        .map(|g| g.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                })
+                .collect();
+            format!(
+                r#"<layer id="{}" name="{}" width="{}" height="{}"{}>
+  <data encoding="csv">
+{}
+</data>
+ </layer>"#,
+                i + 1,
+                l.name,
+                w,
+                h,
+                if l.is_visible { "" } else { r#" visible="0""# },
+                rows.join(",\n")
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" tiledversion="1.10.0" orientation="orthogonal" renderorder="right-down" width="{}" height="{}" tilewidth="8" tileheight="8" infinite="0">
+ {}
+ {}
+</map>"#,
+        w, h, tilesets, layers
+    )
+}
+
+#[test]
+fn tmx_levels_match_json() {
+    let mut paths: Vec<String> = fs::read_dir("tiled")
+        .unwrap()
+        .map(|e| e.unwrap().path().to_string_lossy().to_string())
+        .filter(|p| p.ends_with(".json"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let json = load(&fs::read_to_string(&path).unwrap());
+        let tmx = load(&to_tmx(&TiledLevel::from_map(&json)));
+        assert_eq!(
+            TiledLevel::from_map(&json).layers,
+            TiledLevel::from_map(&tmx).layers,
+            "{}",
+            path
+        );
+
+        let (a, b) = (
+            LevelInfo::new(&json).unwrap(),
+            LevelInfo::new(&tmx).unwrap(),
+        );
+        assert_eq!(a.size, b.size, "{}", path);
+        assert_eq!(a.key, b.key, "{}", path);
+        assert_eq!(a.door, b.door, "{}", path);
+        assert_eq!(a.agent, b.agent, "{}", path);
+        assert_eq!(a.walls, b.walls, "{}", path);
+        assert_eq!(a.spikes.len(), b.spikes.len(), "{}", path);
+        for (ea, eb) in a.enemies.iter().zip(b.enemies.iter()) {
+            assert_eq!(ea.pos, eb.pos, "{}", path);
+            assert_eq!(ea.properties, eb.properties, "{}", path);
+        }
+    }
+}
+
+#[test]
+fn object_layer_level() {
+    // 6x5 room with walls around the border
+    let walls = [
+        1, 1, 1, 1, 1, 1, //
+        1, 0, 0, 0, 0, 1, //
+        1, 0, 0, 0, 0, 1, //
+        1, 0, 0, 0, 0, 1, //
+        1, 1, 1, 1, 1, 1,
+    ];
+    let csv: Vec<String> = walls.iter().map(|g: &u32| g.to_string()).collect();
+    let tmx = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="6" height="5" tilewidth="8" tileheight="8">
+ <tileset firstgid="1" name="tileset" tilewidth="8" tileheight="8" tilecount="144" columns="12">
+  <image source="{}" width="100" height="100"/>
+ </tileset>
+ <layer id="1" name="walls" width="6" height="5">
+  <data encoding="csv">{}</data>
+ </layer>
+ <!-- Everything else is placed as objects -->
+ <objectgroup id="2" name="things">
+  <object id="1" name="Spawn" x="8" y="8"><point/></object>
+  <object id="2" type="key" x="32" y="8"><point/></object>
+  <object id="3" class="door" x="40" y="24" width="8" height="8"/>
+  <object id="4" name="patrol" class="crab" x="12" y="20">
+   <properties>
+    <property name="period" type="int" value="2"/>
+   </properties>
+   <polyline points="0,0 24,0 24,8"/>
+  </object>
+  <object id="5" class="spike" x="16" y="24"><point/></object>
+ </objectgroup>
+</map>"#,
+        TILE_SET_NAME,
+        csv.join(",")
+    );
+
+    let info = LevelInfo::new(&load(&tmx)).unwrap();
+    assert_eq!(info.size, (6, 5));
+    assert_eq!(info.agent, (1, 1));
+    assert_eq!(info.key, (4, 1));
+    assert_eq!(info.door, (5, 3));
+    assert!(info.is_wall((0, 0)));
+    assert!(!info.is_wall((2, 2)));
+    assert_eq!(info.spikes.len(), 1);
+    assert_eq!(info.spikes[0].pos, (2, 3));
+
+    assert_eq!(info.enemies.len(), 1);
+    let crab = &info.enemies[0];
+    assert_eq!(crab.pos, (1, 2));
+    assert_eq!(crab.property("enemy"), Some("crab"));
+    assert_eq!(crab.property("period"), Some("2"));
+    assert_eq!(crab.property("path"), Some("1,2;4,2;4,3"));
+}
+
+#[test]
+fn base64_layers() {
+    // Two gids, 3 and 258, little endian u32s
+    let tmx = format!(
+        r#"<map width="2" height="1" tilewidth="8" tileheight="8">
+ <tileset firstgid="1" name="tileset" tilewidth="8" tileheight="8" tilecount="300" columns="12">
+  <image source="{}" width="100" height="100"/>
+ </tileset>
+ <layer name="walls" width="2" height="1"><data encoding="base64">
+   AwAAAAIBAAA=
+ </data></layer>
+ <objectgroup name="objects">
+  <object name="player" x="0" y="0"/>
+  <object name="key" x="8" y="0"/>
+  <object name="door" x="8" y="0"/>
+ </objectgroup>
+</map>"#,
+        TILE_SET_NAME
+    );
+
+    let level = TiledLevel::from_map(&load(&tmx));
+    assert_eq!(level.layer("walls").unwrap().data, vec![3, 258]);
+}
+
+#[test]
+fn unreadable_maps_are_errors() {
+    let textures = [(TILE_SET_NAME, Texture2D::empty())];
+
+    // Half written, like a file Tiled is still saving
+    let json = fs::read_to_string("tiled/lvl2.json").unwrap();
+    assert!(level::load_map(&json[..json.len() / 2], &textures).is_err());
+    let tmx = to_tmx(&TiledLevel::from_map(&load(&json)));
+    assert!(level::load_map(&tmx[..tmx.len() / 2], &textures).is_err());
+
+    let level_with = |data: &str, objects: &str| {
+        let tmx = format!(
+            r#"<map width="2" height="1" tilewidth="8" tileheight="8">
+ <tileset firstgid="1" name="tileset" tilewidth="8" tileheight="8" tilecount="144" columns="12">
+  <image source="{}" width="100" height="100"/>
+ </tileset>
+ <layer name="walls" width="2" height="1"><data encoding="csv">{}</data></layer>
+ <objectgroup name="objects">{}</objectgroup>
+</map>"#,
+            TILE_SET_NAME, data, objects
+        );
+        LevelInfo::new(&load(&tmx))
+    };
+    let objects = r#"<object name="player" x="0" y="0"/><object name="key" x="8" y="0"/>"#;
+    let door = r#"<object name="door" x="8" y="0"/>"#;
+    assert!(level_with("0,0", &format!("{}{}", objects, door)).is_ok());
+    assert_eq!(level_with("0,0", objects).err().unwrap(), "No doors found");
+    assert!(level_with("0", &format!("{}{}", objects, door)).is_err());
+}
+
+#[test]
+fn flipped_tile_objects_and_diagonal_paths() {
+    let level_with = |objects: &str| {
+        let tmx = format!(
+            r#"<map width="3" height="3" tilewidth="8" tileheight="8">
+ <tileset firstgid="1" name="tileset" tilewidth="8" tileheight="8" tilecount="144" columns="12">
+  <image source="{}" width="100" height="100"/>
+  <tile id="76"><properties><property name="axis" value="vertical"/></properties></tile>
+ </tileset>
+ <layer name="walls" width="3" height="3"><data encoding="csv">0,0,0,0,0,0,0,0,0</data></layer>
+ <objectgroup name="objects">
+  <object name="player" x="0" y="0"/>
+  <object name="key" x="8" y="0"/>
+  <object name="door" x="16" y="0"/>
+  {}
+ </objectgroup>
+</map>"#,
+            TILE_SET_NAME, objects
+        );
+        LevelInfo::new(&load(&tmx))
+    };
+
+    // Tile 76 flipped horizontally, the top bit of the gid
+    let flipped = format!(
+        r#"<object type="crab" gid="{}" x="8" y="24" width="8" height="8"/>"#,
+        77 | 0x8000_0000u32
+    );
+    let info = level_with(&flipped).unwrap();
+    assert_eq!(info.enemies.len(), 1);
+    assert_eq!(info.enemies[0].pos, (1, 2));
+    assert_eq!(info.enemies[0].value, 76);
+    assert_eq!(info.enemies[0].property("axis"), Some("vertical"));
+
+    let path = |points: &str| {
+        format!(
+            r#"<object type="crab" x="4" y="4"><polyline points="{}"/></object>"#,
+            points
+        )
+    };
+    assert!(level_with(&path("0,0 16,0 16,16")).is_ok());
+    let error = level_with(&path("0,0 16,16")).err().unwrap();
+    assert!(error.contains("isn't horizontal or vertical"), "{}", error);
+}